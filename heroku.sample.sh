@@ -3,16 +3,25 @@ set -e
 readonly APP_NAME=sprig-demo-api
 
 readonly ACCESS_CONTROL_ALLOW_ORIGIN=https://s8sato.github.io
+readonly ADMIN_EMAILS=#### # comma-separated, who may view job status
+readonly ARCHIVE_RETENTION_DAYS=0 # keep archives forever
+readonly ATTACHMENT_DIR=/var/lib/sprig/attachments # mount persistent storage here
+readonly ATTACHMENT_LIMIT=10485760 # bytes per file
 readonly CMD_HELP_DIR=/usr/local/share/help
 # DATABASE_URL: Given by Heroku Postgres
 readonly EMAIL_API=SparkPost # or SendGrid
 readonly EMAIL_API_KEY=####
+readonly EMAIL_TEMPLATE_DIR=/usr/local/share/email
+readonly HOUSEKEEPING_INTERVAL=3600 # seconds
 readonly INDENT='    '
 readonly IS_CROSS_ORIGIN=true
+readonly MENTION_EMAIL=true # notify users mentioned in comments
+readonly PERMISSION_REQUEST_EMAIL=true # notify users of permission requests
 # PORT: Given by Heroku
 readonly SECRET_KEY=####
 readonly SENDER_NAME='Sprig'
 readonly SENDING_EMAIL_ADDRESS=####
+readonly TRASH_RETENTION_DAYS=30 # 0 to keep deleted tasks forever
 
 heroku create $APP_NAME
 heroku stack:set container
@@ -20,15 +29,24 @@ heroku stack:set container
 heroku addons:create heroku-postgresql:hobby-dev
 
 heroku config:set ACCESS_CONTROL_ALLOW_ORIGIN=$ACCESS_CONTROL_ALLOW_ORIGIN
+heroku config:set ADMIN_EMAILS=$ADMIN_EMAILS
+heroku config:set ARCHIVE_RETENTION_DAYS=$ARCHIVE_RETENTION_DAYS
+heroku config:set ATTACHMENT_DIR=$ATTACHMENT_DIR
+heroku config:set ATTACHMENT_LIMIT=$ATTACHMENT_LIMIT
 heroku config:set CMD_HELP_DIR=$CMD_HELP_DIR
 # DATABASE_URL: Given by Heroku Postgres
 heroku config:set EMAIL_API=$EMAIL_API
 heroku config:set EMAIL_API_KEY=$EMAIL_API_KEY
+heroku config:set EMAIL_TEMPLATE_DIR=$EMAIL_TEMPLATE_DIR
+heroku config:set HOUSEKEEPING_INTERVAL=$HOUSEKEEPING_INTERVAL
 heroku config:set INDENT="$INDENT"
 heroku config:set IS_CROSS_ORIGIN=$IS_CROSS_ORIGIN
+heroku config:set MENTION_EMAIL=$MENTION_EMAIL
+heroku config:set PERMISSION_REQUEST_EMAIL=$PERMISSION_REQUEST_EMAIL
 # PORT: Given by Heroku
 heroku config:set SECRET_KEY=$SECRET_KEY
 heroku config:set SENDER_NAME="$SENDER_NAME"
 heroku config:set SENDING_EMAIL_ADDRESS=$SENDING_EMAIL_ADDRESS
+heroku config:set TRASH_RETENTION_DAYS=$TRASH_RETENTION_DAYS
 
 git push heroku <branchname>:main
@@ -0,0 +1,2 @@
+ALTER TABLE users ADD COLUMN locale VARCHAR NOT NULL DEFAULT 'en';
+ALTER TABLE invitations ADD COLUMN locale VARCHAR NOT NULL DEFAULT 'en';
@@ -0,0 +1 @@
+ALTER TABLE invitations ADD COLUMN owner INT REFERENCES users ON DELETE CASCADE;
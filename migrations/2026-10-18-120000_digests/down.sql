@@ -0,0 +1,3 @@
+ALTER TABLE users DROP COLUMN digest_sent_at;
+ALTER TABLE users DROP COLUMN digest_at;
+ALTER TABLE users DROP COLUMN tz;
@@ -0,0 +1,3 @@
+ALTER TABLE users ADD COLUMN tz VARCHAR;
+ALTER TABLE users ADD COLUMN digest_at TIME;
+ALTER TABLE users ADD COLUMN digest_sent_at TIMESTAMP WITH TIME ZONE;
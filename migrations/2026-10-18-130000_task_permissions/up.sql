@@ -0,0 +1,6 @@
+CREATE TABLE task_permissions (
+  subject INT REFERENCES users ON DELETE CASCADE,
+  task INT REFERENCES tasks ON DELETE CASCADE,
+  edit BOOL NOT NULL,
+  PRIMARY KEY (subject, task)
+);
@@ -0,0 +1,16 @@
+CREATE TABLE teams (
+  id SERIAL PRIMARY KEY,
+  name VARCHAR NOT NULL UNIQUE,
+  owner INT NOT NULL REFERENCES users ON DELETE CASCADE
+);
+CREATE TABLE team_members (
+  team INT REFERENCES teams ON DELETE CASCADE,
+  member INT REFERENCES users ON DELETE CASCADE,
+  PRIMARY KEY (team, member)
+);
+CREATE TABLE team_permissions (
+  team INT REFERENCES teams ON DELETE CASCADE,
+  object INT REFERENCES users ON DELETE CASCADE,
+  edit BOOL NOT NULL,
+  PRIMARY KEY (team, object)
+);
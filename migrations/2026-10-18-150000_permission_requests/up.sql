@@ -0,0 +1,7 @@
+CREATE TABLE permission_requests (
+  subject INT REFERENCES users ON DELETE CASCADE,
+  object INT REFERENCES users ON DELETE CASCADE,
+  edit BOOL NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (subject, object)
+);
@@ -0,0 +1,12 @@
+CREATE TABLE audits (
+  id SERIAL PRIMARY KEY,
+  actor INT REFERENCES users ON DELETE SET NULL,
+  owner INT REFERENCES users ON DELETE CASCADE,
+  action VARCHAR NOT NULL,
+  task INT,
+  old_values JSONB,
+  new_values JSONB,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+CREATE INDEX audits_task ON audits (task);
+CREATE INDEX audits_created_at ON audits (created_at);
@@ -0,0 +1,14 @@
+CREATE TABLE task_versions (
+  id SERIAL PRIMARY KEY,
+  task INT NOT NULL REFERENCES tasks ON DELETE CASCADE,
+  title VARCHAR NOT NULL,
+  assign INT NOT NULL REFERENCES users ON DELETE CASCADE,
+  is_starred BOOL NOT NULL,
+  startable TIMESTAMP WITH TIME ZONE,
+  deadline TIMESTAMP WITH TIME ZONE,
+  weight REAL,
+  link VARCHAR,
+  updated_at TIMESTAMP WITH TIME ZONE NOT NULL,
+  replaced_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+CREATE INDEX task_versions_task ON task_versions (task);
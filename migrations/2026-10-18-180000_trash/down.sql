@@ -0,0 +1,2 @@
+DROP TABLE trashed_arrows;
+ALTER TABLE tasks DROP COLUMN deleted_at;
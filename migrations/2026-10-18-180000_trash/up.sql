@@ -0,0 +1,6 @@
+ALTER TABLE tasks ADD COLUMN deleted_at TIMESTAMP WITH TIME ZONE;
+CREATE TABLE trashed_arrows (
+  source INT REFERENCES tasks ON DELETE CASCADE,
+  target INT REFERENCES tasks ON DELETE CASCADE,
+  PRIMARY KEY (source, target)
+);
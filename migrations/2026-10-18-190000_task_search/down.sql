@@ -0,0 +1,2 @@
+DROP INDEX tasks_search_idx;
+ALTER TABLE tasks DROP COLUMN search;
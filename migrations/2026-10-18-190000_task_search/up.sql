@@ -0,0 +1,5 @@
+ALTER TABLE tasks ADD COLUMN search TSVECTOR GENERATED ALWAYS AS (
+  setweight(to_tsvector('simple', title), 'A') ||
+  setweight(to_tsvector('simple', coalesce(link, '')), 'B')
+) STORED;
+CREATE INDEX tasks_search_idx ON tasks USING GIN (search);
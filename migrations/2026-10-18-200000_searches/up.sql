@@ -0,0 +1,7 @@
+CREATE TABLE searches (
+  id SERIAL PRIMARY KEY,
+  owner INT NOT NULL REFERENCES users ON DELETE CASCADE,
+  name VARCHAR NOT NULL,
+  condition VARCHAR NOT NULL,
+  UNIQUE (owner, name)
+);
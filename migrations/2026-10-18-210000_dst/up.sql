@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN dst VARCHAR NOT NULL DEFAULT 'earlier';
@@ -0,0 +1,3 @@
+ALTER TABLE users DROP COLUMN default_view;
+ALTER TABLE users DROP COLUMN date_format;
+ALTER TABLE users DROP COLUMN week_start;
@@ -0,0 +1,3 @@
+ALTER TABLE users ADD COLUMN week_start INT NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN date_format VARCHAR NOT NULL DEFAULT 'ymd';
+ALTER TABLE users ADD COLUMN default_view VARCHAR NOT NULL DEFAULT 'home';
@@ -0,0 +1,9 @@
+DROP INDEX tasks_search_idx;
+ALTER TABLE tasks DROP COLUMN search;
+ALTER TABLE tasks ADD COLUMN search TSVECTOR GENERATED ALWAYS AS (
+  setweight(to_tsvector('simple', title), 'A') ||
+  setweight(to_tsvector('simple', coalesce(link, '')), 'B')
+) STORED;
+CREATE INDEX tasks_search_idx ON tasks USING GIN (search);
+ALTER TABLE task_versions DROP COLUMN notes;
+ALTER TABLE tasks DROP COLUMN notes;
@@ -0,0 +1,10 @@
+ALTER TABLE tasks ADD COLUMN notes VARCHAR;
+ALTER TABLE task_versions ADD COLUMN notes VARCHAR;
+DROP INDEX tasks_search_idx;
+ALTER TABLE tasks DROP COLUMN search;
+ALTER TABLE tasks ADD COLUMN search TSVECTOR GENERATED ALWAYS AS (
+  setweight(to_tsvector('simple', title), 'A') ||
+  setweight(to_tsvector('simple', coalesce(link, '')), 'B') ||
+  setweight(to_tsvector('simple', coalesce(notes, '')), 'C')
+) STORED;
+CREATE INDEX tasks_search_idx ON tasks USING GIN (search);
@@ -0,0 +1,2 @@
+ALTER TABLE task_versions DROP COLUMN links;
+DROP TABLE task_links;
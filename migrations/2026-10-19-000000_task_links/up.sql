@@ -0,0 +1,10 @@
+CREATE TABLE task_links (
+  id SERIAL PRIMARY KEY,
+  task INT NOT NULL REFERENCES tasks ON DELETE CASCADE,
+  label VARCHAR,
+  url VARCHAR NOT NULL
+);
+CREATE INDEX task_links_task ON task_links (task);
+-- tasks.link stays as the first link, which listings and searches use
+INSERT INTO task_links (task, url) SELECT id, link FROM tasks WHERE link IS NOT NULL;
+ALTER TABLE task_versions ADD COLUMN links JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1,11 @@
+CREATE TABLE attachments (
+  id SERIAL PRIMARY KEY,
+  task INT NOT NULL REFERENCES tasks ON DELETE CASCADE,
+  name VARCHAR NOT NULL,
+  content_type VARCHAR NOT NULL,
+  size INT NOT NULL,
+  digest VARCHAR NOT NULL,
+  uploaded_by INT NOT NULL REFERENCES users ON DELETE CASCADE,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+CREATE INDEX attachments_task ON attachments (task);
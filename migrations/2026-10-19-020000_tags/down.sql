@@ -0,0 +1,3 @@
+ALTER TABLE task_versions DROP COLUMN tags;
+DROP TABLE task_tags;
+DROP TABLE tags;
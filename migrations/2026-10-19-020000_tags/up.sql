@@ -0,0 +1,13 @@
+CREATE TABLE tags (
+  id SERIAL PRIMARY KEY,
+  owner INT NOT NULL REFERENCES users ON DELETE CASCADE,
+  name VARCHAR NOT NULL,
+  UNIQUE (owner, name)
+);
+CREATE TABLE task_tags (
+  task INT REFERENCES tasks ON DELETE CASCADE,
+  tag INT REFERENCES tags ON DELETE CASCADE,
+  PRIMARY KEY (task, tag)
+);
+CREATE INDEX task_tags_tag ON task_tags (tag);
+ALTER TABLE task_versions ADD COLUMN tags JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1,9 @@
+CREATE TABLE comments (
+  id SERIAL PRIMARY KEY,
+  task INT NOT NULL REFERENCES tasks ON DELETE CASCADE,
+  author INT NOT NULL REFERENCES users ON DELETE CASCADE,
+  body VARCHAR NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+  edited_at TIMESTAMP WITH TIME ZONE
+);
+CREATE INDEX comments_task ON comments (task);
@@ -0,0 +1,10 @@
+CREATE TABLE notifications (
+  id SERIAL PRIMARY KEY,
+  owner INT NOT NULL REFERENCES users ON DELETE CASCADE,
+  actor INT REFERENCES users ON DELETE SET NULL,
+  kind VARCHAR NOT NULL,
+  task INT REFERENCES tasks ON DELETE CASCADE,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+  read_at TIMESTAMP WITH TIME ZONE
+);
+CREATE INDEX notifications_owner ON notifications (owner, read_at);
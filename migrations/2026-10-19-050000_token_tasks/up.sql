@@ -0,0 +1 @@
+ALTER TABLE tokens ADD COLUMN tasks INT[] NOT NULL DEFAULT '{}';
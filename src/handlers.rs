@@ -1,4 +1,4 @@
-mod _email;
+pub mod _email;
 pub mod app;
 pub mod auth;
 pub mod invite;
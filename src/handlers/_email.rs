@@ -1,15 +1,44 @@
+use chrono::{DateTime, NaiveDate, Utc};
 use chrono_tz::Tz;
+use handlebars::Handlebars;
+use once_cell::sync::Lazy;
 use sendgrid::{Mail, SGClient};
+use serde::Serialize;
+use serde_json::json;
 use sparkpost::transmission::{EmailAddress, Message, Recipient, Transmission};
+use std::convert::TryFrom;
 use std::str::FromStr;
 
 use crate::errors;
 use crate::models;
 use crate::utils;
 
+pub const DEFAULT_LOCALE: &str = "en";
+
+// every locale directory must provide {kind}.subject, {kind}.html and {kind}.txt for these
+const KINDS: [&str; 8] = [
+    "invitation",
+    "reset",
+    "password_changed",
+    "email_change",
+    "email_changed",
+    "digest",
+    "permission_request",
+    "mention",
+];
+
+pub static TEMPLATES: Lazy<Templates> = Lazy::new(|| {
+    Templates::load(
+        &utils::env_var("EMAIL_TEMPLATE_DIR"),
+        utils::env_var("SENDER_NAME"),
+        utils::env_var("SENDING_EMAIL_ADDRESS"),
+    )
+});
+
 enum API {
     SendGrid,
     SparkPost,
+    Sink,
 }
 
 pub struct Email {
@@ -17,7 +46,17 @@ pub struct Email {
     from: String,
     to: String,
     subject: String,
-    body: String,
+    html: String,
+    text: String,
+}
+
+pub struct Templates {
+    sender: String,
+    from: String,
+    locales: Vec<String>,
+    subject: Handlebars<'static>,
+    html: Handlebars<'static>,
+    text: Handlebars<'static>,
 }
 
 impl FromStr for API {
@@ -27,6 +66,7 @@ impl FromStr for API {
         match s {
             "SendGrid" => Ok(API::SendGrid),
             "SparkPost" => Ok(API::SparkPost),
+            "Sink" => Ok(API::Sink),
             etc => {
                 println!("Invalid Email API: {}", etc);
                 Err(errors::ServiceError::InternalServerError)
@@ -35,17 +75,107 @@ impl FromStr for API {
     }
 }
 
+impl Templates {
+    pub fn load(dir: &str, sender: String, from: String) -> Self {
+        let mut templates = Self {
+            sender: sender,
+            from: from,
+            locales: Vec::new(),
+            subject: Handlebars::new(),
+            html: Handlebars::new(),
+            text: Handlebars::new(),
+        };
+        templates.subject.register_escape_fn(handlebars::no_escape);
+        templates.text.register_escape_fn(handlebars::no_escape);
+        for entry in std::fs::read_dir(dir).expect("Failed to read email templates.") {
+            let path = entry.expect("Failed to read email templates.").path();
+            if !path.is_dir() {
+                continue;
+            }
+            let locale = path.file_name().unwrap().to_string_lossy().to_string();
+            for kind in KINDS.iter() {
+                let name = format!("{}/{}", locale, kind);
+                let file = |ext: &str| path.join(format!("{}.{}", kind, ext));
+                templates
+                    .subject
+                    .register_template_file(&name, file("subject"))
+                    .and(templates.html.register_template_file(&name, file("html")))
+                    .and(templates.text.register_template_file(&name, file("txt")))
+                    .unwrap_or_else(|e| panic!("Failed to load email template {}: {}", name, e));
+            }
+            templates.locales.push(locale);
+        }
+        if !templates.has(DEFAULT_LOCALE) {
+            panic!("Email templates for {} must be provided", DEFAULT_LOCALE);
+        }
+        templates
+    }
+    pub fn has(&self, locale: &str) -> bool {
+        self.locales.iter().any(|l| l == locale)
+    }
+    fn render<T: Serialize>(
+        &self,
+        locale: &str,
+        kind: &str,
+        data: &T,
+    ) -> Result<(String, String, String), errors::ServiceError> {
+        let locale = if self.has(locale) {
+            locale
+        } else {
+            DEFAULT_LOCALE
+        };
+        let name = format!("{}/{}", locale, kind);
+        let render = |hbs: &Handlebars| {
+            hbs.render(&name, data).map_err(|e| {
+                println!("Email Template Error:\n{:#?}", e);
+                errors::ServiceError::InternalServerError
+            })
+        };
+        Ok((
+            render(&self.subject)?.trim().to_string(),
+            render(&self.html)?,
+            render(&self.text)?,
+        ))
+    }
+    fn email<T: Serialize>(
+        &self,
+        to: String,
+        locale: &str,
+        kind: &str,
+        data: &T,
+    ) -> Result<Email, errors::ServiceError> {
+        let (subject, html, text) = self.render(locale, kind, data)?;
+        Ok(Email {
+            sender: self.sender.clone(),
+            from: self.from.clone(),
+            to: to,
+            subject: subject,
+            html: html,
+            text: text,
+        })
+    }
+}
+
 impl Email {
+    pub fn render<T: Serialize>(
+        to: String,
+        locale: &str,
+        kind: &str,
+        data: &T,
+    ) -> Result<Self, errors::ServiceError> {
+        TEMPLATES.email(to, locale, kind, data)
+    }
     pub fn send(&self) -> Result<(), errors::ServiceError> {
-        let api_key = utils::env_var("EMAIL_API_KEY");
         match utils::env_var("EMAIL_API").parse::<API>()? {
             API::SendGrid => {
+                let api_key = utils::env_var("EMAIL_API_KEY");
                 let mail = Mail::new()
                     .add_from_name(&*self.sender)
                     .add_from(&*self.from)
                     .add_to((&*self.to, &*self.to).into())
                     .add_subject(&*self.subject)
-                    .add_html(&*self.body);
+                    .add_html(&*self.html)
+                    .add_text(&*self.text);
 
                 match SGClient::new(api_key).send(mail) {
                     Ok(res) => {
@@ -59,10 +189,12 @@ impl Email {
                 }
             }
             API::SparkPost => {
+                let api_key = utils::env_var("EMAIL_API_KEY");
                 let mut mail = Message::new(EmailAddress::new(&*self.from, &*self.sender));
                 mail.add_recipient(Recipient::from(&*self.to))
                     .subject(&*self.subject)
-                    .html(&*self.body);
+                    .html(&*self.html)
+                    .text(&*self.text);
 
                 match Transmission::new(api_key).send(&mail) {
                     Ok(res) => {
@@ -75,47 +207,244 @@ impl Email {
                     }
                 }
             }
+            API::Sink => self.sink(std::path::Path::new(&utils::env_var("EMAIL_SINK_DIR"))),
         }
     }
+    /// Sends a notice of what is done already, so that a failure is only logged.
+    pub fn notice<T>(notice: T)
+    where
+        Self: TryFrom<T, Error = errors::ServiceError>,
+    {
+        if let Err(e) = Self::try_from(notice).and_then(|email| email.send()) {
+            println!("Email Error:\n{:#?}", e);
+        }
+    }
+    /// Write the email into a directory instead: for local development and tests.
+    pub fn sink(&self, dir: &std::path::Path) -> Result<(), errors::ServiceError> {
+        let path = dir.join(format!("{}.eml", uuid::Uuid::new_v4()));
+        std::fs::write(
+            path,
+            format!(
+                "From: {} <{}>\nTo: {}\nSubject: {}\n\n{}\n{}",
+                self.sender, self.from, self.to, self.subject, self.text, self.html,
+            ),
+        )?;
+        Ok(())
+    }
 }
 
-impl From<models::Invitation> for Email {
-    fn from(invitation: models::Invitation) -> Self {
-        let sender = utils::env_var("SENDER_NAME");
-        let subject = format!(
-            "{} Sprig",
-            if invitation.forgot_pw {
-                "Reset Password of"
-            } else {
-                "Invitation to"
-            },
-        );
-        let body = format!(
-            "\
-            Your {} key is: <br>
-            <span style=\"font-size: x-large; font-weight: bold;\">{}</span> <br>
-            The key expires on: <br>
-            <span style=\"font-weight: bold;\">{} in {}</span> <br>
-            ",
-            if invitation.forgot_pw {
-                "reset"
-            } else {
-                "register"
-            },
-            invitation.id,
-            invitation
-                .expires_at
-                .with_timezone(&invitation.tz.parse::<Tz>().unwrap())
-                .format("%Y/%m/%d %a %H:%M") // RFC 3339
-                .to_string(),
-            invitation.tz,
+fn format_dt(dt: &DateTime<Utc>, tz: &Tz) -> String {
+    dt.with_timezone(tz)
+        .format("%Y/%m/%d %a %H:%M") // RFC 3339
+        .to_string()
+}
+
+/// In the user's date format, as precisely as their timescale.
+fn format_deadline(dt: &DateTime<Utc>, tz: &Tz, settings: &models::Settings) -> String {
+    let date = settings.date_format.date_pattern();
+    let pattern = match settings.timescale.time_pattern() {
+        Some(time) => format!("{} %a {}", date, time),
+        None => format!("{} %a", date),
+    };
+    dt.with_timezone(tz).format(&pattern).to_string()
+}
+
+impl TryFrom<models::Invitation> for Email {
+    type Error = errors::ServiceError;
+
+    fn try_from(invitation: models::Invitation) -> Result<Self, Self::Error> {
+        let tz = invitation.tz()?;
+        let data = json!({
+            "key": invitation.id,
+            "email": invitation.email,
+            "expires_at": format_dt(&invitation.expires_at, &tz),
+            "tz": invitation.tz,
+        });
+        let kind = match (invitation.owner, invitation.forgot_pw) {
+            (Some(_), _) => "email_change",
+            (None, true) => "reset",
+            (None, false) => "invitation",
+        };
+        Self::render(invitation.email, &invitation.locale, kind, &data)
+    }
+}
+
+pub struct PasswordChanged {
+    pub user: models::User,
+    pub tz: Tz,
+}
+
+impl TryFrom<PasswordChanged> for Email {
+    type Error = errors::ServiceError;
+
+    fn try_from(notice: PasswordChanged) -> Result<Self, Self::Error> {
+        let data = json!({
+            "name": notice.user.name,
+            "changed_at": format_dt(&notice.user.updated_at, &notice.tz),
+            "tz": notice.tz.to_string(),
+        });
+        Self::render(
+            notice.user.email,
+            &notice.user.locale,
+            "password_changed",
+            &data,
+        )
+    }
+}
+
+pub struct EmailChanged {
+    pub old_email: String,
+    pub user: models::User,
+    pub tz: Tz,
+}
+
+impl TryFrom<EmailChanged> for Email {
+    type Error = errors::ServiceError;
+
+    fn try_from(notice: EmailChanged) -> Result<Self, Self::Error> {
+        let data = json!({
+            "name": notice.user.name,
+            "email": notice.user.email,
+            "changed_at": format_dt(&notice.user.updated_at, &notice.tz),
+            "tz": notice.tz.to_string(),
+        });
+        Self::render(
+            notice.old_email,
+            &notice.user.locale,
+            "email_changed",
+            &data,
+        )
+    }
+}
+
+pub struct Digest {
+    pub user: models::User,
+    pub tz: Tz,
+    pub date: NaiveDate,
+    pub due_today: Vec<models::ResTask>,
+    pub overdue: Vec<models::ResTask>,
+    pub at_risk: Vec<models::ResTask>,
+}
+
+impl TryFrom<Digest> for Email {
+    type Error = errors::ServiceError;
+
+    fn try_from(digest: Digest) -> Result<Self, Self::Error> {
+        digest.email(&TEMPLATES)
+    }
+}
+
+impl Digest {
+    pub fn email(self, templates: &Templates) -> Result<Email, errors::ServiceError> {
+        let tz = self.tz;
+        let settings = models::Settings::from(&self.user);
+        let items = |tasks: &Vec<models::ResTask>| {
+            tasks
+                .iter()
+                .map(|t| {
+                    json!({
+                        "id": t.id,
+                        "title": t.title,
+                        "deadline": t.deadline.map(|dt| format_deadline(&dt, &tz, &settings)).unwrap_or_default(),
+                    })
+                })
+                .collect::<Vec<serde_json::Value>>()
+        };
+        let data = json!({
+            "name": self.user.name,
+            "date": self.date.format(&format!("{} %a", settings.date_format.date_pattern())).to_string(),
+            "due_today": items(&self.due_today),
+            "overdue": items(&self.overdue),
+            "at_risk": items(&self.at_risk),
+        });
+        templates.email(self.user.email, &self.user.locale, "digest", &data)
+    }
+}
+
+pub struct PermissionRequested {
+    pub requester: String,
+    pub edit: bool,
+    pub object: models::User,
+}
+
+impl TryFrom<PermissionRequested> for Email {
+    type Error = errors::ServiceError;
+
+    fn try_from(notice: PermissionRequested) -> Result<Self, Self::Error> {
+        let data = json!({
+            "name": notice.object.name,
+            "requester": notice.requester,
+            "edit": notice.edit,
+        });
+        Self::render(
+            notice.object.email,
+            &notice.object.locale,
+            "permission_request",
+            &data,
+        )
+    }
+}
+
+pub struct Mentioned {
+    pub author: String,
+    pub task: i32,
+    pub title: String,
+    pub body: String,
+    pub object: models::User,
+}
+
+impl TryFrom<Mentioned> for Email {
+    type Error = errors::ServiceError;
+
+    fn try_from(notice: Mentioned) -> Result<Self, Self::Error> {
+        let data = json!({
+            "name": notice.object.name,
+            "author": notice.author,
+            "task": notice.task,
+            "title": notice.title,
+            "body": notice.body,
+        });
+        Self::render(notice.object.email, &notice.object.locale, "mention", &data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_render_every_kind() {
+        let templates = Templates::load(
+            "src/handlers/_email_templates",
+            String::from("Sprig"),
+            String::from("sprig@example.com"),
         );
-        Self {
-            sender: sender,
-            from: utils::env_var("SENDING_EMAIL_ADDRESS"),
-            to: invitation.email,
-            subject: subject,
-            body: body,
+        let item = json!({ "id": 1, "title": "task1", "deadline": "2021/01/01 Fri" });
+        let data = json!({
+            "key": "00000000-0000-0000-0000-000000000000",
+            "email": "satun@example.com",
+            "expires_at": "2021/01/01 Fri 00:00",
+            "tz": "Asia/Tokyo",
+            "name": "satun",
+            "changed_at": "2021/01/01 Fri 00:00",
+            "date": "2021/01/01 Fri",
+            "due_today": [item],
+            "overdue": [item],
+            "at_risk": [item],
+            "requester": "alice",
+            "edit": true,
+            "author": "alice",
+            "task": 1,
+            "body": "@satun",
+        });
+        for locale in &["en", "ja"] {
+            assert!(templates.has(locale));
+            for kind in KINDS.iter() {
+                let (subject, html, text) = templates.render(locale, kind, &data).unwrap();
+                assert!(!subject.is_empty(), "{}/{}", locale, kind);
+                assert!(!html.is_empty(), "{}/{}", locale, kind);
+                assert!(!text.is_empty(), "{}/{}", locale, kind);
+            }
         }
     }
 }
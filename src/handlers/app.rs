@@ -1,7 +1,14 @@
+mod _page;
 mod _parser;
+pub mod attachment;
+pub mod comment;
 pub mod delete;
 pub mod exec;
 pub mod focus;
+pub mod history;
 pub mod home;
+pub mod jobs;
+pub mod notification;
 pub mod star;
 pub mod text;
+pub mod trash;
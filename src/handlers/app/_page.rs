@@ -0,0 +1,252 @@
+use diesel::dsl::sql;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_builder::BoxedSelectStatement;
+use diesel::sql_types::{Bool, Integer, Text};
+use serde::{Deserialize, Serialize};
+
+use crate::errors;
+use crate::models;
+
+/// Number of tasks on a page by default and at most.
+const LIMIT: i64 = 100;
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Page {
+    pub sort: Option<(Sort, bool)>, // descending if true
+    pub after: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    Deadline,
+    Weight,
+    Created,
+    Updated,
+    Title,
+}
+
+#[derive(Serialize)]
+pub struct ResPage {
+    pub tasks: Vec<models::ResTask>,
+    pub next: Option<String>,
+    pub total: i64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub buckets: Vec<models::Bucket>,
+}
+
+impl Page {
+    /// Orders the query, skips up to the cursor and takes one more than the limit.
+    /// Without a sort key, tasks are ranked by `lexemes` if any, otherwise starred first.
+    /// The cursor must be a task `user` may view, lest the order leak others' tasks.
+    pub fn apply<'a, ST, QS>(
+        &self,
+        query: BoxedSelectStatement<'a, ST, QS, Pg>,
+        lexemes: Option<String>,
+        user: &models::AuthedUser,
+        conn: &models::Conn,
+    ) -> Result<BoxedSelectStatement<'a, ST, QS, Pg>, errors::ServiceError> {
+        let after = self.after(user, conn)?;
+        let mut query = query;
+        if let (None, Some(lexemes)) = (self.sort, lexemes) {
+            let rank = |t: &str| format!("ts_rank({}.search, to_tsquery('simple', ", t);
+            query = query.order(
+                sql::<Text>(&rank("tasks"))
+                    .bind::<Text, _>(lexemes.clone())
+                    .sql(")) DESC, tasks.updated_at DESC, tasks.id DESC"),
+            );
+            if let Some(tid) = after {
+                query = query.filter(
+                    sql::<Bool>(&format!("({}", rank("tasks")))
+                        .bind::<Text, _>(lexemes.clone())
+                        .sql(&format!(
+                            ")), tasks.updated_at, tasks.id) < (SELECT {}",
+                            rank("t")
+                        ))
+                        .bind::<Text, _>(lexemes)
+                        .sql(")), t.updated_at, t.id FROM tasks t WHERE t.id = ")
+                        .bind::<Integer, _>(tid)
+                        .sql(")"),
+                );
+            }
+        } else {
+            let (keys, desc) = self.keys();
+            let (direction, compare) = if desc { ("DESC", "<") } else { ("ASC", ">") };
+            let on = |t: &str| {
+                keys.iter()
+                    .chain(["{}.id"].iter())
+                    .map(|k| k.replace("{}", t))
+                    .collect::<Vec<String>>()
+            };
+            query = query.order(sql::<Text>(
+                &on("tasks")
+                    .iter()
+                    .map(|k| format!("{} {}", k, direction))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+            ));
+            if let Some(tid) = after {
+                query = query.filter(
+                    sql::<Bool>(&format!(
+                        "({}) {} (SELECT {} FROM tasks t WHERE t.id = ",
+                        on("tasks").join(", "),
+                        compare,
+                        on("t").join(", "),
+                    ))
+                    .bind::<Integer, _>(tid)
+                    .sql(")"),
+                );
+            }
+        }
+        Ok(query.limit(self.limit() + 1))
+    }
+    /// Cuts the tasks loaded by `apply` down to a page.
+    pub fn cut(&self, mut tasks: Vec<models::ResTask>, total: i64) -> ResPage {
+        let mut next = None;
+        if self.limit() < tasks.len() as i64 {
+            tasks.truncate(self.limit() as usize);
+            next = tasks.last().map(|t| t.id.to_string());
+        }
+        ResPage {
+            tasks: tasks,
+            next: next,
+            total: total,
+            buckets: Vec::new(),
+        }
+    }
+    /// Sort keys on table `{}` except for the id, with nulls last in either direction.
+    fn keys(&self) -> (Vec<&str>, bool) {
+        match self.sort {
+            None => (vec!["{}.is_starred", "{}.updated_at"], true),
+            Some((Sort::Deadline, false)) => (vec!["coalesce({}.deadline, 'infinity')"], false),
+            Some((Sort::Deadline, true)) => (vec!["coalesce({}.deadline, '-infinity')"], true),
+            Some((Sort::Weight, false)) => (vec!["coalesce({}.weight, 'Infinity')"], false),
+            Some((Sort::Weight, true)) => (vec!["coalesce({}.weight, '-Infinity')"], true),
+            Some((Sort::Created, desc)) => (vec!["{}.created_at"], desc),
+            Some((Sort::Updated, desc)) => (vec!["{}.updated_at"], desc),
+            Some((Sort::Title, desc)) => (vec!["{}.title"], desc),
+        }
+    }
+    fn after(
+        &self,
+        user: &models::AuthedUser,
+        conn: &models::Conn,
+    ) -> Result<Option<i32>, errors::ServiceError> {
+        use crate::schema::tasks::dsl::{id, tasks};
+
+        let invalid = || errors::ServiceError::BadRequest("cursor invalid.".into());
+        match &self.after {
+            None => Ok(None),
+            Some(cursor) => {
+                let tid = cursor.parse::<i32>().map_err(|_| invalid())?;
+                // the cursor may have been trashed since, but must still be one's to view
+                tasks
+                    .find(tid)
+                    .filter(user.permitted(false, conn)?.filter())
+                    .select(id)
+                    .first::<i32>(conn)
+                    .optional()?
+                    .map(Some)
+                    .ok_or_else(invalid)
+            }
+        }
+    }
+    fn limit(&self) -> i64 {
+        self.limit.unwrap_or(LIMIT).clamp(1, LIMIT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing;
+
+    fn res_tasks(ids: std::ops::Range<i32>) -> Vec<models::ResTask> {
+        ids.map(|id| models::ResTask {
+            id: id,
+            ..Default::default()
+        })
+        .collect()
+    }
+
+    #[test]
+    fn t_cut() {
+        let page = Page {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let res = page.cut(res_tasks(0..3), 5);
+        assert_eq!(
+            res.tasks.iter().map(|t| t.id).collect::<Vec<i32>>(),
+            vec![0, 1]
+        );
+        assert_eq!(res.next, Some("1".into()));
+        assert_eq!(res.total, 5);
+        let res = page.cut(res_tasks(0..2), 2);
+        assert_eq!(res.tasks.len(), 2);
+        assert_eq!(res.next, None);
+        let res = Page::default().cut(res_tasks(0..101), 101);
+        assert_eq!(res.tasks.len(), 100);
+        assert_eq!(res.next, Some("99".into()));
+    }
+
+    #[test]
+    fn t_apply() {
+        use crate::schema::permissions::dsl::permissions;
+        use crate::schema::tasks::dsl::{assign, id, tasks, title};
+
+        let pool = match testing::pool() {
+            Some(pool) => pool,
+            None => return,
+        };
+        let conn = pool.get().unwrap();
+        let viewer = testing::user("page_viewer", &conn);
+        let other = testing::user("page_other", &conn);
+        let insert = |title_: &str, assign_: i32| {
+            diesel::insert_into(tasks)
+                .values((title.eq(title_), assign.eq(assign_)))
+                .returning(id)
+                .get_result::<i32>(&conn)
+                .unwrap()
+        };
+        let a = insert("a", viewer.id);
+        let b = insert("b", viewer.id);
+        let c = insert("c", viewer.id);
+        let hidden = insert("bb", other.id);
+        let page = |after: Option<i32>, user: &models::AuthedUser| {
+            let page = Page {
+                sort: Some((Sort::Title, false)),
+                after: after.map(|tid| tid.to_string()),
+                limit: Some(1),
+            };
+            page.apply(
+                tasks.filter(assign.eq(viewer.id)).select(id).into_boxed(),
+                None,
+                user,
+                &conn,
+            )
+            .and_then(|query| Ok(query.load::<i32>(&conn)?))
+        };
+        assert_eq!(page(None, &viewer).unwrap(), vec![a, b]);
+        assert_eq!(page(Some(a), &viewer).unwrap(), vec![b, c]);
+        assert!(page(Some(c), &viewer).unwrap().is_empty());
+        assert!(page(Some(hidden), &viewer).is_err());
+        diesel::insert_into(permissions)
+            .values(&models::Permission {
+                subject: viewer.id,
+                object: other.id,
+                edit: false,
+            })
+            .execute(&conn)
+            .unwrap();
+        assert_eq!(page(Some(hidden), &viewer).unwrap(), vec![c]);
+        assert!(Page {
+            after: Some("x".into()),
+            ..Default::default()
+        }
+        .after(&viewer, &conn)
+        .is_err());
+    }
+}
@@ -1,15 +1,17 @@
 use combine::parser::{
-    char::{digit, newline, space, string},
+    char::{digit, lower, newline, space, string},
     combinator::recognize,
     repeat::{skip_count_min_max, take_until},
 };
 use combine::{
-    attempt, choice, eof, from_str, many, many1, optional, parser, satisfy, sep_by1, skip_many,
-    skip_many1, token, Parser, Stream,
+    attempt, choice, eof, from_str, look_ahead, many, many1, not_followed_by, optional, parser,
+    satisfy, sep_by1, skip_many, skip_many1, token, Parser, Stream,
 };
 use once_cell::sync::Lazy;
 use std::str::FromStr;
 
+use super::_page::{Page, Sort};
+use super::comment;
 use super::text::{self, *};
 use crate::errors;
 use crate::models;
@@ -41,6 +43,15 @@ impl FromStr for Req {
     }
 }
 
+impl FromStr for ReqSearch {
+    type Err = errors::ServiceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (con, page) = condition_().and(page_()).skip(eof()).parse(s)?.0;
+        Ok(ReqSearch::Condition(con, page))
+    }
+}
+
 impl text::ReqBody {
     pub fn wash(&self) -> String {
         self.remove_comments()
@@ -75,6 +86,23 @@ impl text::ReqBody {
     }
 }
 
+impl comment::ReqBody {
+    /// Names following `@` at the start of words, each once.
+    pub fn mentions(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        for word in self.body.split_whitespace() {
+            if let Ok((name, _)) = token('@').with(namings1_()).parse(word) {
+                // a period ending the sentence
+                let name = name.trim_end_matches('.').to_string();
+                if !name.is_empty() && !names.contains(&name) {
+                    names.push(name)
+                }
+            }
+        }
+        names
+    }
+}
+
 parser! {
     fn req_[Input]()(Input) -> Req
     where [ Input: Stream<Token = char> ] {
@@ -94,11 +122,26 @@ parser! {
     fn req_cmd_[Input]()(Input) -> ReqCmd
     where [ Input: Stream<Token = char> ] {
         choice((
+            attempt(string("undo")).with(spaces1_().with(token('#')).with(non_nega_i_()))
+            .and(optional(attempt(spaces1_().with(non_nega_i_()))))
+            .map(|(t, v)| ReqCmd::Undo(ReqUndo {task: t, version: v})),
             token('u').with(optional(spaces1_().with(req_user_()))).map(|opt| {
                 ReqCmd::User(opt.unwrap_or(ReqUser::Help))
             }),
-            token('s').with(optional(spaces1_().with(condition_()))).map(|opt| {
-                ReqCmd::Search(opt.map(|con| ReqSearch::Condition(con)).unwrap_or(ReqSearch::Help))
+            token('s').with(optional(spaces1_().with(condition_()).and(page_()))).map(|opt| {
+                ReqCmd::Search(opt.map(|(con, page)| ReqSearch::Condition(con, page)).unwrap_or(ReqSearch::Help))
+            }),
+            token('v').with(optional(spaces1_().with(req_view_()))).map(|opt| {
+                ReqCmd::View(opt.unwrap_or(ReqView::Help))
+            }),
+            attempt(string("team")).with(optional(spaces1_().with(req_team_()))).map(|opt| {
+                ReqCmd::Team(opt.unwrap_or(ReqTeam::Help))
+            }),
+            attempt(string("tag")).with(optional(spaces1_().with(req_tag_()))).map(|opt| {
+                ReqCmd::Tag(opt.unwrap_or(ReqTag::Help))
+            }),
+            attempt(string("log")).with(optional(spaces1_().with(req_log_()))).map(|opt| {
+                ReqCmd::Log(opt.unwrap_or_default())
             }),
             attempt(string("tutorial")).map(|_| ReqCmd::Tutorial),
             attempt(string("coffee")).map(|_| ReqCmd::Coffee),
@@ -119,20 +162,135 @@ parser! {
     where [ Input: Stream<Token = char> ] {
         let permission = |c: char, p: Option<bool>| {
             token(c).with(spaces1_().with(namings1_()))
-            .map(move |x| ReqModify::Permission(ReqPermission {user: x, permission: p}))
+            .and(optional(attempt(spaces1_().with(token('#')).with(non_nega_i_()))))
+            .map(move |(x, t)| ReqModify::Permission(ReqPermission {user: x, task: t, permission: p}))
         };
         choice((
-            token('e').with(spaces1_().with(email_())).map(|x| ReqModify::Email(x)),
+            token('e').with(spaces1_().with(choice((
+                email_().map(|x| ReqModify::Email(x)),
+                key_().map(|x| ReqModify::EmailConfirm(x)),
+            )))),
             token('p').with(spaces1_().with(password_set_())).map(|x| ReqModify::Password(x)),
             token('n').with(spaces1_().with(namings1_())).map(|x| ReqModify::Name(x)),
             token('t').with(spaces1_().with(timescale_())).map(|x| ReqModify::Timescale(x)),
+            token('l').with(spaces1_().with(namings1_())).map(|x| ReqModify::Locale(x)),
+            token('d').with(optional(spaces1_().with(time_()))).map(|x| ReqModify::Digest(x)),
+            token('s').with(spaces1_().with(dst_())).map(|x| ReqModify::Dst(x)),
+            token('z').with(spaces1_().with(ascii_graphics1_())).map(|x| ReqModify::Tz(x)),
+            token('w').with(spaces1_().with(weekday_())).map(|x| ReqModify::WeekStart(x)),
+            token('f').with(spaces1_().with(date_format_())).map(|x| ReqModify::DateFormat(x)),
+            token('h').with(spaces1_().with(namings1_())).map(|x| ReqModify::DefaultView(x)),
             token('a').with(many(spaces1_().with(req_allocation_()))).map(|x| ReqModify::Allocations(x)),
             permission('0', None),
             permission('1', Some(false)),
             permission('2', Some(true)),
+            token('r').with(choice((token('1').map(|_| false), token('2').map(|_| true))))
+            .and(spaces1_().with(namings1_()))
+            .map(|(e, x)| ReqModify::RequestPermission(ReqPermissionRequest {user: x, edit: e})),
+            token('y').with(spaces1_().with(namings1_())).map(|x| ReqModify::Approve(x)),
+            token('x').with(spaces1_().with(namings1_())).map(|x| ReqModify::Deny(x)),
+        ))
+    }
+}
+parser! {
+    fn req_team_[Input]()(Input) -> ReqTeam
+    where [ Input: Stream<Token = char> ] {
+        let member = |c: char| {
+            token(c).with(spaces1_().with(namings1_())).and(spaces1_().with(namings1_()))
+            .map(|(t, u)| ReqTeamMember {team: t, user: u})
+        };
+        let permission = |c: char, p: Option<bool>| {
+            token(c).with(spaces1_().with(namings1_()))
+            .map(move |x| ReqTeam::Permission(ReqTeamPermission {team: x, permission: p}))
+        };
+        attempt(token('-').with(choice((
+            token('i').map(|_| ReqTeam::Info),
+            token('c').with(spaces1_().with(namings1_())).map(|x| ReqTeam::Create(x)),
+            token('d').with(spaces1_().with(namings1_())).map(|x| ReqTeam::Delete(x)),
+            member('a').map(|x| ReqTeam::Add(x)),
+            member('r').map(|x| ReqTeam::Remove(x)),
+            permission('0', None),
+            permission('1', Some(false)),
+            permission('2', Some(true)),
+        ))))
+    }
+}
+parser! {
+    fn req_tag_[Input]()(Input) -> ReqTag
+    where [ Input: Stream<Token = char> ] {
+        let pair = |c: char| {
+            token(c).with(spaces1_().with(tag_())).and(spaces1_().with(tag_()))
+            .map(|(f, t)| ReqTagPair {from: f, to: t})
+        };
+        attempt(token('-').with(choice((
+            token('i').map(|_| ReqTag::Info),
+            pair('r').map(|x| ReqTag::Rename(x)),
+            pair('m').map(|x| ReqTag::Merge(x)),
+        ))))
+    }
+}
+parser! {
+    fn req_view_[Input]()(Input) -> ReqView
+    where [ Input: Stream<Token = char> ] {
+        choice((
+            token('-').with(choice((
+                token('i').map(|_| ReqView::Info),
+                token('s').with(spaces1_().with(namings1_()))
+                .and(spaces1_().with(recognize(condition_().and(page_()))))
+                .map(|(n, c)| ReqView::Save(ReqViewSave {name: n, condition: c})),
+                token('d').with(spaces1_().with(namings1_())).map(|x| ReqView::Delete(x)),
+            ))),
+            namings1_().and(optional(attempt(spaces1_().with(token('@')).with(namings1_()))))
+            .and(page_())
+            .map(|((n, o), p)| ReqView::Run(ReqViewRun {name: n, owner: o, page: p})),
+        ))
+    }
+}
+parser! {
+    fn req_log_[Input]()(Input) -> ReqLog
+    where [ Input: Stream<Token = char> ] {
+        sep_by1(req_log_item_(), spaces1_())
+    }
+}
+parser! {
+    fn req_log_item_[Input]()(Input) -> ReqLog
+    where [ Input: Stream<Token = char> ] {
+        choice((
+            token('#').with(non_nega_i_()).map(|x| {
+                let mut req = ReqLog::default();
+                req.task = Some(x);
+                req
+            }),
+            token('@').with(namings1_()).map(|x| {
+                let mut req = ReqLog::default();
+                req.actor = Some(x);
+                req
+            }),
+            attempt(
+                optional(datetime_().skip(token('<'))).skip(token('c')).and(optional(token('<').with(datetime_())))
+            ).map(|(l, r)| {
+                let mut req = ReqLog::default();
+                req.created_at = (l, r);
+                req
+            }),
         ))
     }
 }
+impl std::iter::Extend<Self> for ReqLog {
+    fn extend<T: IntoIterator<Item = Self>>(&mut self, iter: T) {
+        for item in iter {
+            if self.task.lt(&item.task) {
+                self.task = item.task
+            };
+            if self.actor.lt(&item.actor) {
+                self.actor = item.actor
+            };
+            if self.created_at.lt(&item.created_at) {
+                self.created_at = item.created_at
+            };
+        }
+    }
+}
 parser! {
     fn email_[Input]()(Input) -> String
     where [ Input: Stream<Token = char> ] {
@@ -143,6 +301,12 @@ parser! {
         )))
     }
 }
+parser! {
+    fn key_[Input]()(Input) -> uuid::Uuid
+    where [ Input: Stream<Token = char> ] {
+        from_str(many1::<String, _, _>(satisfy(|c: char| c.is_ascii_hexdigit() || c == '-')))
+    }
+}
 parser! {
     fn password_set_[Input]()(Input) -> PasswordSet
     where [ Input: Stream<Token = char> ] {
@@ -157,20 +321,42 @@ parser! {
     }
 }
 parser! {
-    fn timescale_[Input]()(Input) -> Timescale
+    fn timescale_[Input]()(Input) -> models::Timescale
+    where [ Input: Stream<Token = char> ] {
+        let p = |t: models::Timescale| attempt(string(t.as_str())).map(move |_| t.clone());
+        choice((
+            p(models::Timescale::Year),
+            p(models::Timescale::Quarter),
+            p(models::Timescale::Month),
+            p(models::Timescale::Week),
+            p(models::Timescale::Day),
+            p(models::Timescale::Hours),
+            p(models::Timescale::Hour),
+            p(models::Timescale::Minutes),
+            p(models::Timescale::Minute),
+            p(models::Timescale::Second),
+        ))
+    }
+}
+parser! {
+    fn dst_[Input]()(Input) -> models::Dst
+    where [ Input: Stream<Token = char> ] {
+        let p = |d: models::Dst| attempt(string(d.as_str())).map(move |_| d);
+        choice((
+            p(models::Dst::Earlier),
+            p(models::Dst::Later),
+            p(models::Dst::Strict),
+        ))
+    }
+}
+parser! {
+    fn date_format_[Input]()(Input) -> models::DateFormat
     where [ Input: Stream<Token = char> ] {
-        let p = |t: Timescale| attempt(string(t.as_str())).map(move |_| t.clone());
+        let p = |f: models::DateFormat| attempt(string(f.as_str())).map(move |_| f);
         choice((
-            p(Timescale::Year),
-            p(Timescale::Quarter),
-            p(Timescale::Month),
-            p(Timescale::Week),
-            p(Timescale::Day),
-            p(Timescale::Hours),
-            p(Timescale::Hour),
-            p(Timescale::Minutes),
-            p(Timescale::Minute),
-            p(Timescale::Second),
+            p(models::DateFormat::Ymd),
+            p(models::DateFormat::Mdy),
+            p(models::DateFormat::Dmy),
         ))
     }
 }
@@ -186,21 +372,48 @@ parser! {
     }
 }
 parser! {
-    fn condition_[Input]()(Input) -> Condition
+    fn condition_[Input]()(Input) -> Query
+    where [ Input: Stream<Token = char> ] {
+        sep_by1(
+            condition_and_(),
+            attempt(skip_many(space()).skip(token('|')).skip(skip_many(space()))),
+        ).map(|mut qs: Vec<Query>| {
+            if qs.len() == 1 { qs.pop().unwrap() } else { Query::Or(qs) }
+        })
+    }
+}
+parser! {
+    fn condition_and_[Input]()(Input) -> Query
     where [ Input: Stream<Token = char> ] {
-        sep_by1(condition_item_(), spaces1_())
+        condition_factor_().and(many(attempt(spaces1_().with(condition_factor_()))))
+        .map(|(q, mut qs): (Query, Vec<Query>)| {
+            if qs.is_empty() { q } else { qs.insert(0, q); Query::And(qs) }
+        })
+    }
+}
+parser! {
+    fn condition_factor_[Input]()(Input) -> Query
+    where [ Input: Stream<Token = char> ] {
+        choice((
+            token('!').with(condition_factor_()).map(|q| Query::Not(Box::new(q))),
+            token('(').skip(skip_many(space()))
+            .with(condition_())
+            .skip(skip_many(space())).skip(token(')')),
+            condition_item_().map(|con| Query::Term(con)),
+        ))
     }
 }
 parser! {
     fn condition_item_[Input]()(Input) -> Condition
     where [ Input: Stream<Token = char> ] {
         choice((
-            attempt(optional(token('@').or(token('&'))).and(expression_())).map(|(opt, expr)| {
+            attempt(optional(token('@').or(token('&')).or(token('%'))).and(expression_())).map(|(opt, expr)| {
                 let mut condition = Condition::default();
                 match opt {
                     None => condition.title = Some(expr),
                     Some('@') => condition.assign = Some(expr),
                     Some('&') => condition.link = Some(expr),
+                    Some('%') => condition.notes = Some(expr),
                     _ => unreachable!()
                 }
                 condition
@@ -210,13 +423,6 @@ parser! {
                 condition.boolean = x;
                 condition
             }),
-            attempt(
-                optional(non_nega_i_().skip(token('<'))).skip(token('#')).and(optional(token('<').with(non_nega_i_())))
-            ).map(|(l, r)| {
-                let mut condition = Condition::default();
-                condition.context = (l, r);
-                condition
-            }),
             attempt(
                 optional(non_nega_f_().skip(token('<'))).skip(token('$')).and(optional(token('<').with(non_nega_f_())))
             ).map(|(l, r)| {
@@ -242,39 +448,75 @@ parser! {
                 }
                 condition
             }),
+            attempt(
+                optional(non_nega_i_().skip(token('<'))).skip(token('#')).and(optional(token('<').with(non_nega_i_())))
+            ).map(|(l, r)| {
+                let mut condition = Condition::default();
+                condition.context = (l, r);
+                condition
+            }),
+            attempt(token('+').with(tag_())).map(|t| {
+                let mut condition = Condition::default();
+                condition.tag = Some(t);
+                condition
+            }),
         ))
     }
 }
-impl std::iter::Extend<Self> for Condition {
+parser! {
+    fn page_[Input]()(Input) -> Page
+    where [ Input: Stream<Token = char> ] {
+        many(attempt(spaces1_().with(page_item_())))
+    }
+}
+parser! {
+    fn page_item_[Input]()(Input) -> Page
+    where [ Input: Stream<Token = char> ] {
+        choice((
+            attempt(token('^').with(optional(token('!'))).and(choice([
+                token('d'),
+                token('w'),
+                token('c'),
+                token('u'),
+                token('t'),
+            ]))).map(|(desc, c)| {
+                let mut page = Page::default();
+                let sort = match c {
+                    'd' => Sort::Deadline,
+                    'w' => Sort::Weight,
+                    'c' => Sort::Created,
+                    'u' => Sort::Updated,
+                    't' => Sort::Title,
+                    _ => unreachable!()
+                };
+                page.sort = Some((sort, desc.is_some()));
+                page
+            }),
+            attempt(token('^').with(non_nega_i_())).map(|n| {
+                let mut page = Page::default();
+                page.limit = Some(n as i64);
+                page
+            }),
+            token('>').with(many1(satisfy(|c: char| c.is_ascii_alphanumeric()))).map(|cursor| {
+                let mut page = Page::default();
+                page.after = Some(cursor);
+                page
+            }),
+        ))
+    }
+}
+impl std::iter::Extend<Self> for Page {
     fn extend<T: IntoIterator<Item = Self>>(&mut self, iter: T) {
+        // the latter wins
         for item in iter {
-            self.boolean.extend(std::iter::once(item.boolean));
-            if self.context.lt(&item.context) {
-                self.context = item.context
-            };
-            if self.weight.lt(&item.weight) {
-                self.weight = item.weight
-            };
-            if self.startable.lt(&item.startable) {
-                self.startable = item.startable
+            if item.sort.is_some() {
+                self.sort = item.sort
             };
-            if self.deadline.lt(&item.deadline) {
-                self.deadline = item.deadline
+            if item.after.is_some() {
+                self.after = item.after
             };
-            if self.created_at.lt(&item.created_at) {
-                self.created_at = item.created_at
-            };
-            if self.updated_at.lt(&item.updated_at) {
-                self.updated_at = item.updated_at
-            };
-            if self.title.lt(&item.title) {
-                self.title = item.title
-            };
-            if self.assign.lt(&item.assign) {
-                self.assign = item.assign
-            };
-            if self.link.lt(&item.link) {
-                self.link = item.link
+            if item.limit.is_some() {
+                self.limit = item.limit
             };
         }
     }
@@ -359,23 +601,79 @@ parser! {
                 models::EasyDateTime {
                     date: Some(d),
                     time: Some(t),
+                    rel: None,
                 }
             }),
             date_().map(|d| {
                 models::EasyDateTime {
                     date: Some(d),
                     time: None,
+                    rel: None,
                 }
             }),
             time_().map(|t| {
                 models::EasyDateTime {
                     date: None,
                     time: Some(t),
+                    rel: None,
+                }
+            }),
+            rel_().and(optional(attempt(token('T').with(time_())))).map(|(r, t)| {
+                models::EasyDateTime {
+                    date: None,
+                    time: t,
+                    rel: Some(r),
                 }
             }),
         ))
     }
 }
+parser! {
+    fn rel_[Input]()(Input) -> models::EasyRel
+    where [ Input: Stream<Token = char> ] {
+        choice((
+            attempt(token('+').with(days_())).map(|d| models::EasyRel::Days(d)),
+            attempt(string("today")).map(|_| models::EasyRel::Days(0)),
+            attempt(string("tomorrow")).map(|_| models::EasyRel::Days(1)),
+            attempt(string("eow")).map(|_| models::EasyRel::EndOfWeek),
+            attempt(string("eom")).map(|_| models::EasyRel::EndOfMonth),
+            weekday_().map(|w| models::EasyRel::Weekday(w)),
+            attempt(token('#').with(non_nega_i_()).and(optional(attempt(token('+').with(days_())))))
+            .map(|(tid, d)| models::EasyRel::Task(tid, d.unwrap_or_default())),
+        ))
+    }
+}
+parser! {
+    fn weekday_[Input]()(Input) -> u32
+    where [ Input: Stream<Token = char> ] {
+        // either abbreviated or in full, but then not followed by a lowercase letter, unlike `T`
+        let p = |s: &'static str, rest: &'static str, w: u32| {
+            attempt(string(s).skip(optional(attempt(string(rest)))).skip(not_followed_by(lower())))
+            .map(move |_| w)
+        };
+        choice((
+            p("mon", "day", 0),
+            p("tue", "sday", 1),
+            p("wed", "nesday", 2),
+            p("thu", "rsday", 3),
+            p("fri", "day", 4),
+            p("sat", "urday", 5),
+            p("sun", "day", 6),
+        ))
+    }
+}
+parser! {
+    fn days_[Input]()(Input) -> i64
+    where [ Input: Stream<Token = char> ] {
+        non_nega_i_().and(choice([
+            token('d'),
+            token('w'),
+        ])).map(|(n, c)| match c {
+            'w' => 7 * n as i64,
+            _ => n as i64,
+        })
+    }
+}
 parser! {
     fn date_[Input]()(Input) -> models::EasyDate
     where [ Input: Stream<Token = char> ] {
@@ -406,18 +704,46 @@ parser! {
     where [ Input: Stream<Token = char> ] {
         indent_()
         .and(attribute_())
-        .and(optional(attempt(newline().with(inline_spaces_().with(link_())))))
+        .and(many(attempt(newline().with(req_link_()))))
+        .and(many(attempt(newline().with(note_()))))
         .skip(choice((
             newline().map(|_| ()),
             eof(),
         )))
-        .map(|((indent, attribute), link)| ReqTask {
+        .map(|(((indent, attribute), links), notes): (_, Vec<String>)| ReqTask {
             indent: indent,
             attribute: attribute,
-            link: link,
+            links: links,
+            notes: if notes.is_empty() { None } else { Some(notes.join("\n")) },
+        })
+    }
+}
+parser! {
+    fn req_link_[Input]()(Input) -> models::Link
+    where [ Input: Stream<Token = char> ] {
+        inline_spaces_()
+        .with(optional(attempt(
+            token('[')
+            .with(many1(satisfy(|c: char| c != ']' && c != '\n')))
+            .skip(token(']'))
+            .skip(inline_spaces1_())
+        )))
+        .and(link_())
+        .map(|(label, url)| models::Link {
+            label: label,
+            url: url,
         })
     }
 }
+parser! {
+    fn note_[Input]()(Input) -> String
+    where [ Input: Stream<Token = char> ] {
+        inline_spaces_()
+        .skip(token('|'))
+        .skip(optional(token(' ')))
+        .with(many(satisfy(|c: char| c != '\n')))
+    }
+}
 parser! {
     fn indent_[Input]()(Input) -> i32
     where [ Input: Stream<Token = char> ] {
@@ -465,6 +791,11 @@ impl std::iter::Extend<Self> for Attribute {
             if let Some(x) = item.deadline {
                 self.deadline = Some(x)
             };
+            for x in item.tags {
+                if !self.tags.contains(&x) {
+                    self.tags.push(x)
+                }
+            }
             if !item.title.is_empty() {
                 if !self.title.is_empty() {
                     self.title.push(' ');
@@ -489,6 +820,11 @@ parser! {
                 attribute.is_starred = true;
                 attribute
             }),
+            attempt(datetime_().skip(token('-')).skip(look_ahead(space().map(|_| ()).or(eof())))).map(|dt| {
+                let mut attribute = Attribute::default();
+                attribute.startable = Some(dt);
+                attribute
+            }),
             token('#').with(non_nega_i_()).map(|i| {
                 let mut attribute = Attribute::default();
                 attribute.id = Some(i);
@@ -509,14 +845,14 @@ parser! {
                 attribute.deadline = Some(dt);
                 attribute
             }),
-            token('[').with(graphics1_not_joint_()).map(|g| {
+            attempt(token('+').with(tag_())).map(|t| {
                 let mut attribute = Attribute::default();
-                attribute.joint_tails.push(g);
+                attribute.tags.push(t);
                 attribute
             }),
-            attempt(datetime_().skip(token('-'))).map(|dt| {
+            token('[').with(graphics1_not_joint_()).map(|g| {
                 let mut attribute = Attribute::default();
-                attribute.startable = Some(dt);
+                attribute.joint_tails.push(g);
                 attribute
             }),
             attempt(graphics1_not_joint_().skip(token(']'))).map(|g| {
@@ -618,6 +954,12 @@ parser! {
         many1(graphic_())
     }
 }
+parser! {
+    fn tag_[Input]()(Input) -> String
+    where [ Input: Stream<Token = char> ] {
+        many1(satisfy(|c: char| is_graphic(c) && !"[]()|".contains(c)))
+    }
+}
 parser! {
     fn graphic_not_joint_[Input]()(Input) -> char
     where [ Input: Stream<Token = char> ] {
@@ -680,6 +1022,18 @@ mod tests {
         assert!(t_10.is_err());
     }
     #[test]
+    fn t_mentions() {
+        let mentions = |s: &str| comment::ReqBody { body: s.into() }.mentions();
+        assert_eq!(
+            mentions("@alice can you check this with @bob.b? Thanks, @alice."),
+            vec![String::from("alice"), String::from("bob.b")]
+        );
+        assert_eq!(
+            mentions("mail me at me@example.com or @"),
+            Vec::<String>::new()
+        );
+    }
+    #[test]
     fn t_req_cmd_() {
         let t_01 = req_cmd_().easy_parse("u");
         let t_02 = req_cmd_().easy_parse("s");
@@ -696,7 +1050,10 @@ mod tests {
         assert_eq!(
             t_05,
             Ok((
-                ReqCmd::Search(ReqSearch::Condition(Condition::default())),
+                ReqCmd::Search(ReqSearch::Condition(
+                    Query::Term(Condition::default()),
+                    Page::default()
+                )),
                 ""
             ))
         );
@@ -705,47 +1062,348 @@ mod tests {
         assert!(t_12.is_err());
     }
     #[test]
-    fn t_req_user_() {
-        let t_10 = req_user_().easy_parse("x");
+    fn t_req_view_() {
+        let t_01 = req_cmd_().easy_parse("v");
+        let t_02 = req_cmd_().easy_parse("v -i");
+        let t_03 = req_cmd_().easy_parse(r#"v -s bugs "bug" | -s ^d"#);
+        let t_04 = req_cmd_().easy_parse("v -d bugs");
+        let t_05 = req_cmd_().easy_parse("v bugs");
+        let t_06 = req_cmd_().easy_parse("v bugs @alice ^!w >12");
+        let t_10 = req_cmd_().easy_parse("v -s bugs");
+        let t_11 = "\"bug\" ^d".parse::<ReqSearch>();
+        let t_12 = "\"bug\" @".parse::<ReqSearch>();
+        assert_eq!(t_01, Ok((ReqCmd::View(ReqView::Help), "")));
+        assert_eq!(t_02, Ok((ReqCmd::View(ReqView::Info), "")));
+        assert_eq!(
+            t_03,
+            Ok((
+                ReqCmd::View(ReqView::Save(ReqViewSave {
+                    name: String::from("bugs"),
+                    condition: String::from(r#""bug" | -s ^d"#),
+                })),
+                ""
+            ))
+        );
+        assert_eq!(
+            t_04,
+            Ok((ReqCmd::View(ReqView::Delete(String::from("bugs"))), ""))
+        );
+        assert_eq!(
+            t_05,
+            Ok((
+                ReqCmd::View(ReqView::Run(ReqViewRun {
+                    name: String::from("bugs"),
+                    owner: None,
+                    page: Page::default(),
+                })),
+                ""
+            ))
+        );
+        assert_eq!(
+            t_06,
+            Ok((
+                ReqCmd::View(ReqView::Run(ReqViewRun {
+                    name: String::from("bugs"),
+                    owner: Some(String::from("alice")),
+                    page: Page {
+                        sort: Some((Sort::Weight, true)),
+                        after: Some(String::from("12")),
+                        limit: None,
+                    },
+                })),
+                ""
+            ))
+        );
         assert!(t_10.is_err());
+        assert!(t_11.is_ok());
+        assert!(t_12.is_err());
     }
     #[test]
-    fn t_req_modify_() {
-        let t_00 = req_modify_().easy_parse("n   satun__   etc...   ");
-        let t_10 = req_modify_().easy_parse("");
-        let t_11 = req_modify_().easy_parse(" ");
-        let t_12 = req_modify_().easy_parse("x");
+    fn t_req_team_() {
+        let t_00 = req_cmd_().easy_parse("team");
+        let t_01 = req_cmd_().easy_parse("team -a dev satun");
+        let t_02 = req_cmd_().easy_parse("team -2 dev");
+        let t_10 = req_cmd_().easy_parse("team -a dev");
+        assert_eq!(t_00, Ok((ReqCmd::Team(ReqTeam::Help), "")));
         assert_eq!(
-            t_00,
-            Ok((ReqModify::Name(String::from("satun__")), "   etc...   "))
+            t_01,
+            Ok((
+                ReqCmd::Team(ReqTeam::Add(ReqTeamMember {
+                    team: String::from("dev"),
+                    user: String::from("satun"),
+                })),
+                ""
+            ))
+        );
+        assert_eq!(
+            t_02,
+            Ok((
+                ReqCmd::Team(ReqTeam::Permission(ReqTeamPermission {
+                    team: String::from("dev"),
+                    permission: Some(true),
+                })),
+                ""
+            ))
         );
         assert!(t_10.is_err());
-        assert!(t_11.is_err());
-        assert!(t_12.is_err());
     }
     #[test]
-    fn t_password_set_() {
-        let t_00 = password_set_()
-            .easy_parse(r##"old!"#$%&'()*+,-./   new0123456789   confirmation:;<=>?@   etc..."##);
-        let t_10 = password_set_().easy_parse("");
-        let t_11 = password_set_().easy_parse("   old new confirmation");
-        let t_12 = password_set_().easy_parse("old new_without_confirmation");
-        let t_13 = password_set_().easy_parse("ぱすわーどに　和文字　とな？");
+    fn t_req_tag_() {
+        let t_00 = req_cmd_().easy_parse("tag");
+        let t_01 = req_cmd_().easy_parse("tag -i");
+        let t_02 = req_cmd_().easy_parse("tag -r wip doing");
+        let t_03 = req_cmd_().easy_parse("tag -m todo doing");
+        let t_10 = req_cmd_().easy_parse("tag -r wip");
+        assert_eq!(t_00, Ok((ReqCmd::Tag(ReqTag::Help), "")));
+        assert_eq!(t_01, Ok((ReqCmd::Tag(ReqTag::Info), "")));
         assert_eq!(
-            t_00,
+            t_02,
             Ok((
-                PasswordSet {
-                    old: String::from(r##"old!"#$%&'()*+,-./"##),
-                    new: String::from(r##"new0123456789"##),
-                    confirmation: String::from(r##"confirmation:;<=>?@"##),
-                },
-                "   etc..."
+                ReqCmd::Tag(ReqTag::Rename(ReqTagPair {
+                    from: String::from("wip"),
+                    to: String::from("doing"),
+                })),
+                ""
+            ))
+        );
+        assert_eq!(
+            t_03,
+            Ok((
+                ReqCmd::Tag(ReqTag::Merge(ReqTagPair {
+                    from: String::from("todo"),
+                    to: String::from("doing"),
+                })),
+                ""
             ))
         );
         assert!(t_10.is_err());
-        assert!(t_11.is_err());
-        assert!(t_12.is_err());
-        assert!(t_13.is_err());
+    }
+    #[test]
+    fn t_req_log_() {
+        let t_00 = req_cmd_().easy_parse("log");
+        let t_01 = req_cmd_().easy_parse("log #12 @satun 2021//<c");
+        let t_10 = req_cmd_().easy_parse("log x");
+        assert_eq!(t_00, Ok((ReqCmd::Log(ReqLog::default()), "")));
+        assert_eq!(
+            t_01,
+            Ok((
+                ReqCmd::Log(ReqLog {
+                    task: Some(12),
+                    actor: Some(String::from("satun")),
+                    created_at: (
+                        Some(models::EasyDateTime {
+                            date: Some(models::EasyDate {
+                                y: Some(2021),
+                                m: None,
+                                d: None,
+                            }),
+                            time: None,
+                            rel: None,
+                        }),
+                        None
+                    ),
+                }),
+                ""
+            ))
+        );
+        assert!(t_10.is_err());
+    }
+    #[test]
+    fn t_req_undo_() {
+        let t_00 = req_cmd_().easy_parse("undo #12");
+        let t_01 = req_cmd_().easy_parse("undo #12 34");
+        let t_10 = req_cmd_().easy_parse("undo");
+        assert_eq!(
+            t_00,
+            Ok((
+                ReqCmd::Undo(ReqUndo {
+                    task: 12,
+                    version: None,
+                }),
+                ""
+            ))
+        );
+        assert_eq!(
+            t_01,
+            Ok((
+                ReqCmd::Undo(ReqUndo {
+                    task: 12,
+                    version: Some(34),
+                }),
+                ""
+            ))
+        );
+        assert!(t_10.is_err());
+    }
+    #[test]
+    fn t_req_user_() {
+        let t_10 = req_user_().easy_parse("x");
+        assert!(t_10.is_err());
+    }
+    #[test]
+    fn t_req_modify_() {
+        let t_00 = req_modify_().easy_parse("n   satun__   etc...   ");
+        let t_01 = req_modify_().easy_parse("s later");
+        let t_02 = req_modify_().easy_parse("z Asia/Tokyo");
+        let t_03 = req_modify_().easy_parse("w sun");
+        let t_04 = req_modify_().easy_parse("f dmy");
+        let t_05 = req_modify_().easy_parse("h bugs");
+        let t_10 = req_modify_().easy_parse("");
+        let t_11 = req_modify_().easy_parse(" ");
+        let t_12 = req_modify_().easy_parse("x");
+        let t_13 = req_modify_().easy_parse("s lately");
+        let t_14 = req_modify_().easy_parse("w sunday");
+        let t_15 = req_modify_().easy_parse("f iso");
+        assert_eq!(
+            t_00,
+            Ok((ReqModify::Name(String::from("satun__")), "   etc...   "))
+        );
+        assert_eq!(t_01, Ok((ReqModify::Dst(models::Dst::Later), "")));
+        assert_eq!(t_02, Ok((ReqModify::Tz(String::from("Asia/Tokyo")), "")));
+        assert_eq!(t_03, Ok((ReqModify::WeekStart(6), "")));
+        assert_eq!(
+            t_04,
+            Ok((ReqModify::DateFormat(models::DateFormat::Dmy), ""))
+        );
+        assert_eq!(t_05, Ok((ReqModify::DefaultView(String::from("bugs")), "")));
+        assert!(t_10.is_err());
+        assert!(t_11.is_err());
+        assert!(t_12.is_err());
+        assert!(t_13.is_err());
+        assert_eq!(t_14, Ok((ReqModify::WeekStart(6), "")));
+        assert!(t_15.is_err());
+    }
+    #[test]
+    fn t_req_modify_permission() {
+        let t_00 = req_modify_().easy_parse("1 satun");
+        let t_01 = req_modify_().easy_parse("2 satun #12");
+        let t_02 = req_modify_().easy_parse("0 satun #12 etc...");
+        let t_10 = req_modify_().easy_parse("1 #12");
+        assert_eq!(
+            t_00,
+            Ok((
+                ReqModify::Permission(ReqPermission {
+                    user: String::from("satun"),
+                    task: None,
+                    permission: Some(false),
+                }),
+                ""
+            ))
+        );
+        assert_eq!(
+            t_01,
+            Ok((
+                ReqModify::Permission(ReqPermission {
+                    user: String::from("satun"),
+                    task: Some(12),
+                    permission: Some(true),
+                }),
+                ""
+            ))
+        );
+        assert_eq!(
+            t_02,
+            Ok((
+                ReqModify::Permission(ReqPermission {
+                    user: String::from("satun"),
+                    task: Some(12),
+                    permission: None,
+                }),
+                " etc..."
+            ))
+        );
+        assert!(t_10.is_err());
+    }
+    #[test]
+    fn t_req_modify_permission_request() {
+        let t_00 = req_modify_().easy_parse("r2 satun");
+        let t_01 = req_modify_().easy_parse("y satun");
+        let t_02 = req_modify_().easy_parse("x satun");
+        let t_10 = req_modify_().easy_parse("r0 satun");
+        assert_eq!(
+            t_00,
+            Ok((
+                ReqModify::RequestPermission(ReqPermissionRequest {
+                    user: String::from("satun"),
+                    edit: true,
+                }),
+                ""
+            ))
+        );
+        assert_eq!(t_01, Ok((ReqModify::Approve(String::from("satun")), "")));
+        assert_eq!(t_02, Ok((ReqModify::Deny(String::from("satun")), "")));
+        assert!(t_10.is_err());
+    }
+    #[test]
+    fn t_req_modify_locale() {
+        let t_00 = req_modify_().easy_parse("l ja");
+        let t_10 = req_modify_().easy_parse("l");
+        assert_eq!(t_00, Ok((ReqModify::Locale(String::from("ja")), "")));
+        assert!(t_10.is_err());
+    }
+    #[test]
+    fn t_req_modify_email() {
+        let t_00 = req_modify_().easy_parse("e someone@example.com");
+        let t_01 = req_modify_().easy_parse("e 67e55044-10b1-426f-9247-bb680e5fe0c8");
+        let t_10 = req_modify_().easy_parse("e someone");
+        let t_11 = req_modify_().easy_parse("e 67e55044-10b1");
+        assert_eq!(
+            t_00,
+            Ok((ReqModify::Email(String::from("someone@example.com")), ""))
+        );
+        assert_eq!(
+            t_01,
+            Ok((
+                ReqModify::EmailConfirm(
+                    "67e55044-10b1-426f-9247-bb680e5fe0c8".parse().unwrap()
+                ),
+                ""
+            ))
+        );
+        assert!(t_10.is_err());
+        assert!(t_11.is_err());
+    }
+    #[test]
+    fn t_req_modify_digest() {
+        let t_00 = req_modify_().easy_parse("d 7:30");
+        let t_01 = req_modify_().easy_parse("d");
+        let t_10 = req_modify_().easy_parse("d 7");
+        assert_eq!(
+            t_00,
+            Ok((
+                ReqModify::Digest(Some(models::EasyTime {
+                    h: Some(7),
+                    m: Some(30),
+                })),
+                ""
+            ))
+        );
+        assert_eq!(t_01, Ok((ReqModify::Digest(None), "")));
+        assert!(t_10.is_err());
+    }
+    #[test]
+    fn t_password_set_() {
+        let t_00 = password_set_()
+            .easy_parse(r##"old!"#$%&'()*+,-./   new0123456789   confirmation:;<=>?@   etc..."##);
+        let t_10 = password_set_().easy_parse("");
+        let t_11 = password_set_().easy_parse("   old new confirmation");
+        let t_12 = password_set_().easy_parse("old new_without_confirmation");
+        let t_13 = password_set_().easy_parse("ぱすわーどに　和文字　とな？");
+        assert_eq!(
+            t_00,
+            Ok((
+                PasswordSet {
+                    old: String::from(r##"old!"#$%&'()*+,-./"##),
+                    new: String::from(r##"new0123456789"##),
+                    confirmation: String::from(r##"confirmation:;<=>?@"##),
+                },
+                "   etc..."
+            ))
+        );
+        assert!(t_10.is_err());
+        assert!(t_11.is_err());
+        assert!(t_12.is_err());
+        assert!(t_13.is_err());
     }
     #[test]
     fn t_timescale_() {
@@ -754,7 +1412,7 @@ mod tests {
         let t_11 = timescale_().easy_parse("   15m");
         let t_12 = timescale_().easy_parse("y");
         let t_13 = timescale_().easy_parse("恒河沙");
-        assert_eq!(t_00, Ok((Timescale::Minutes, "m   etc...")));
+        assert_eq!(t_00, Ok((models::Timescale::Minutes, "m   etc...")));
         assert!(t_10.is_err());
         assert!(t_11.is_err());
         assert!(t_12.is_err());
@@ -762,172 +1420,347 @@ mod tests {
     }
     #[test]
     fn t_condition_() {
+        let t_01 = condition_().easy_parse("(+work|+home) !+later");
         let t_02 = condition_().easy_parse("# $");
         let t_03 = condition_()
             .easy_parse(r##"333<#<777 -a!s -l .5<$<24 s<15: /12/<d c 2021//<u<//30T6:"##);
         let t_04 = condition_().easy_parse(
             r##"333<#<777 -a!s -l .5<$<24 s<15: /12/<d c 2021//<u<//30T6: "tit le" @r#"double"quoted"man"# &r".*domain\.com.*\?page=[1-5]#(frag|ment)""##
         );
+        let t_05 = condition_().easy_parse(r##"!(-a|-s) "title""##);
+        let t_06 = condition_().easy_parse(r##"( "a" | !"b" )|"c""##);
+        let t_07 = condition_().easy_parse(r##""one" "two" | &"domain""##);
+        let t_08 = condition_().easy_parse("fri<d<#12+1w #12<s");
+        let t_09 = condition_().easy_parse(r##"%"meeting" !%r"^draft""##);
         let t_10 = condition_().easy_parse(" title");
         let t_11 = condition_().easy_parse(" ");
         let t_12 = condition_().easy_parse("");
+        let t_13 = condition_().easy_parse("(-a");
+        let t_14 = condition_().easy_parse("-a |");
+        let t_15 = condition_().easy_parse(r##""c++" %"!!!""##);
+        let title = |s: &str| {
+            Query::Term(Condition {
+                title: Some(text::Expression::Words(vec![String::from(s)])),
+                ..Default::default()
+            })
+        };
+        let tag = |s: &str| {
+            Query::Term(Condition {
+                tag: Some(String::from(s)),
+                ..Default::default()
+            })
+        };
         assert_eq!(
-            t_02,
+            t_01,
             Ok((
-                Condition {
-                    boolean: Boolean {
-                        is_archived: None,
-                        is_starred: None,
-                        is_leaf: None,
-                        is_root: None,
-                    },
-                    context: (None, None),
-                    weight: (None, None),
-                    startable: (None, None),
-                    deadline: (None, None),
-                    created_at: (None, None),
-                    updated_at: (None, None),
-                    title: None,
-                    assign: None,
-                    link: None,
-                },
+                Query::And(vec![
+                    Query::Or(vec![tag("work"), tag("home")]),
+                    Query::Not(Box::new(tag("later"))),
+                ]),
                 ""
             ))
         );
         assert_eq!(
-            t_03,
+            t_02,
             Ok((
+                Query::And(vec![
+                    Query::Term(Condition::default()),
+                    Query::Term(Condition::default()),
+                ]),
+                ""
+            ))
+        );
+        let dt = |date: Option<models::EasyDate>, time: Option<models::EasyTime>| {
+            Some(models::EasyDateTime {
+                date: date,
+                time: time,
+                rel: None,
+            })
+        };
+        let terms = || {
+            vec![
+                Condition {
+                    context: (Some(333), Some(777)),
+                    ..Default::default()
+                },
                 Condition {
                     boolean: Boolean {
                         is_archived: Some(true),
                         is_starred: Some(false),
-                        is_leaf: Some(true),
+                        is_leaf: None,
                         is_root: None,
                     },
-                    context: (Some(333), Some(777)),
+                    ..Default::default()
+                },
+                Condition {
+                    boolean: Boolean {
+                        is_leaf: Some(true),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                Condition {
                     weight: (Some(0.5), Some(24.0)),
+                    ..Default::default()
+                },
+                Condition {
                     startable: (
                         None,
-                        Some(models::EasyDateTime {
-                            date: None,
-                            time: Some(models::EasyTime {
+                        dt(
+                            None,
+                            Some(models::EasyTime {
                                 h: Some(15),
                                 m: None,
                             }),
-                        })
+                        ),
                     ),
+                    ..Default::default()
+                },
+                Condition {
                     deadline: (
-                        Some(models::EasyDateTime {
-                            date: Some(models::EasyDate {
+                        dt(
+                            Some(models::EasyDate {
                                 y: None,
                                 m: Some(12),
                                 d: None,
                             }),
-                            time: None,
-                        }),
-                        None
+                            None,
+                        ),
+                        None,
                     ),
-                    created_at: (None, None),
+                    ..Default::default()
+                },
+                Condition::default(),
+                Condition {
                     updated_at: (
-                        Some(models::EasyDateTime {
-                            date: Some(models::EasyDate {
+                        dt(
+                            Some(models::EasyDate {
                                 y: Some(2021),
                                 m: None,
                                 d: None,
                             }),
-                            time: None,
-                        }),
-                        Some(models::EasyDateTime {
-                            date: Some(models::EasyDate {
+                            None,
+                        ),
+                        dt(
+                            Some(models::EasyDate {
                                 y: None,
                                 m: None,
                                 d: Some(30),
                             }),
-                            time: Some(models::EasyTime {
+                            Some(models::EasyTime {
                                 h: Some(6),
                                 m: None,
                             }),
-                        })
+                        ),
                     ),
-                    title: None,
-                    assign: None,
-                    link: None,
+                    ..Default::default()
                 },
+            ]
+        };
+        assert_eq!(
+            t_03,
+            Ok((
+                Query::And(terms().into_iter().map(Query::Term).collect()),
                 ""
             ))
         );
+        let mut terms = terms();
+        terms.push(Condition {
+            title: Some(text::Expression::Words(vec![
+                String::from("tit"),
+                String::from("le"),
+            ])),
+            ..Default::default()
+        });
+        terms.push(Condition {
+            assign: Some(text::Expression::Regex(String::from(
+                r#"double"quoted"man"#,
+            ))),
+            ..Default::default()
+        });
+        terms.push(Condition {
+            link: Some(text::Expression::Regex(String::from(
+                r".*domain\.com.*\?page=[1-5]#(frag|ment)",
+            ))),
+            ..Default::default()
+        });
         assert_eq!(
             t_04,
+            Ok((Query::And(terms.into_iter().map(Query::Term).collect()), ""))
+        );
+        let archived = Query::Term(Condition {
+            boolean: Boolean {
+                is_archived: Some(true),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let starred = Query::Term(Condition {
+            boolean: Boolean {
+                is_starred: Some(true),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        assert_eq!(
+            t_05,
             Ok((
-                Condition {
-                    boolean: Boolean {
-                        is_archived: Some(true),
-                        is_starred: Some(false),
-                        is_leaf: Some(true),
-                        is_root: None,
-                    },
-                    context: (Some(333), Some(777)),
-                    weight: (Some(0.5), Some(24.0)),
-                    startable: (
-                        None,
-                        Some(models::EasyDateTime {
-                            date: None,
-                            time: Some(models::EasyTime {
-                                h: Some(15),
-                                m: None,
-                            }),
-                        })
-                    ),
-                    deadline: (
-                        Some(models::EasyDateTime {
-                            date: Some(models::EasyDate {
-                                y: None,
-                                m: Some(12),
-                                d: None,
-                            }),
-                            time: None,
-                        }),
-                        None
-                    ),
-                    created_at: (None, None),
-                    updated_at: (
-                        Some(models::EasyDateTime {
-                            date: Some(models::EasyDate {
-                                y: Some(2021),
-                                m: None,
-                                d: None,
-                            }),
-                            time: None,
-                        }),
-                        Some(models::EasyDateTime {
-                            date: Some(models::EasyDate {
-                                y: None,
-                                m: None,
-                                d: Some(30),
-                            }),
-                            time: Some(models::EasyTime {
-                                h: Some(6),
-                                m: None,
-                            }),
-                        })
-                    ),
-                    title: Some(text::Expression::Words(vec![
-                        String::from("tit"),
-                        String::from("le"),
-                    ])),
-                    assign: Some(text::Expression::Regex(String::from(
-                        r#"double"quoted"man"#
-                    ))),
-                    link: Some(text::Expression::Regex(String::from(
-                        r".*domain\.com.*\?page=[1-5]#(frag|ment)"
-                    ))),
-                },
+                Query::And(vec![
+                    Query::Not(Box::new(Query::Or(vec![archived, starred]))),
+                    title("title"),
+                ]),
+                ""
+            ))
+        );
+        assert_eq!(
+            t_06,
+            Ok((
+                Query::Or(vec![
+                    Query::Or(vec![title("a"), Query::Not(Box::new(title("b")))]),
+                    title("c"),
+                ]),
+                ""
+            ))
+        );
+        assert_eq!(
+            t_07,
+            Ok((
+                Query::Or(vec![
+                    Query::And(vec![title("one"), title("two")]),
+                    Query::Term(Condition {
+                        link: Some(text::Expression::Words(vec![String::from("domain")])),
+                        ..Default::default()
+                    }),
+                ]),
+                ""
+            ))
+        );
+        let rel = |r| {
+            Some(models::EasyDateTime {
+                date: None,
+                time: None,
+                rel: Some(r),
+            })
+        };
+        assert_eq!(
+            t_08,
+            Ok((
+                Query::And(vec![
+                    Query::Term(Condition {
+                        deadline: (
+                            rel(models::EasyRel::Weekday(4)),
+                            rel(models::EasyRel::Task(12, 7))
+                        ),
+                        ..Default::default()
+                    }),
+                    Query::Term(Condition {
+                        startable: (rel(models::EasyRel::Task(12, 0)), None),
+                        ..Default::default()
+                    }),
+                ]),
+                ""
+            ))
+        );
+        assert_eq!(
+            t_09,
+            Ok((
+                Query::And(vec![
+                    Query::Term(Condition {
+                        notes: Some(text::Expression::Words(vec![String::from("meeting")])),
+                        ..Default::default()
+                    }),
+                    Query::Not(Box::new(Query::Term(Condition {
+                        notes: Some(text::Expression::Regex(String::from("^draft"))),
+                        ..Default::default()
+                    }))),
+                ]),
                 ""
             ))
         );
         assert!(t_10.is_err());
         assert!(t_11.is_err());
         assert!(t_12.is_err());
+        assert!(t_13.is_err());
+        assert!(t_14.is_err());
+        // a word of symbols only makes no lexeme, and symbols are matched literally
+        let t_15 = match t_15 {
+            Ok((Query::And(qs), "")) => qs,
+            t_15 => panic!("{:?}", t_15),
+        };
+        let terms = t_15
+            .iter()
+            .map(|q| match q {
+                Query::Term(con) => (con.lexemes(), con.literals()),
+                q => panic!("{:?}", q),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            terms,
+            vec![
+                (
+                    vec![String::from("'c++':*A")],
+                    vec![("tasks.title", String::from(r"c\+\+"))]
+                ),
+                (
+                    Vec::new(),
+                    vec![("coalesce(tasks.notes, '')", String::from(r"\!\!\!"))]
+                ),
+            ]
+        );
+    }
+    #[test]
+    fn t_page_() {
+        let t_01 = page_().easy_parse(" ^!d ^20 >42");
+        let t_02 = page_().easy_parse(" ^t ^w");
+        let t_03 = page_().easy_parse("");
+        let t_04 = req_cmd_().easy_parse("s -a ^c >7");
+        let t_10 = page_().easy_parse(" ^x");
+        let t_11 = page_().easy_parse(" >");
+        assert_eq!(
+            t_01,
+            Ok((
+                Page {
+                    sort: Some((Sort::Deadline, true)),
+                    after: Some(String::from("42")),
+                    limit: Some(20),
+                },
+                ""
+            ))
+        );
+        assert_eq!(
+            t_02,
+            Ok((
+                Page {
+                    sort: Some((Sort::Weight, false)),
+                    after: None,
+                    limit: None,
+                },
+                ""
+            ))
+        );
+        assert_eq!(t_03, Ok((Page::default(), "")));
+        assert_eq!(
+            t_04,
+            Ok((
+                ReqCmd::Search(ReqSearch::Condition(
+                    Query::Term(Condition {
+                        boolean: Boolean {
+                            is_archived: Some(true),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    }),
+                    Page {
+                        sort: Some((Sort::Created, false)),
+                        after: Some(String::from("7")),
+                        limit: None,
+                    }
+                )),
+                ""
+            ))
+        );
+        assert_eq!(t_10, Ok((Page::default(), " ^x")));
+        assert_eq!(t_11, Ok((Page::default(), " >")));
     }
     #[test]
     fn t_expression_() {
@@ -1060,6 +1893,7 @@ mod tests {
                 models::EasyDateTime {
                     date: Some(models::EasyDate::default()),
                     time: Some(models::EasyTime::default()),
+                    rel: None,
                 },
                 ""
             ))
@@ -1070,6 +1904,7 @@ mod tests {
                 models::EasyDateTime {
                     date: Some(models::EasyDate::default()),
                     time: None,
+                    rel: None,
                 },
                 ""
             ))
@@ -1080,6 +1915,7 @@ mod tests {
                 models::EasyDateTime {
                     date: None,
                     time: Some(models::EasyTime::default()),
+                    rel: None,
                 },
                 ""
             ))
@@ -1092,6 +1928,7 @@ mod tests {
                 models::EasyDateTime {
                     date: Some(models::EasyDate::default()),
                     time: None,
+                    rel: None,
                 },
                 ":"
             ))
@@ -1102,6 +1939,7 @@ mod tests {
                 models::EasyDateTime {
                     date: Some(models::EasyDate::default()),
                     time: None,
+                    rel: None,
                 },
                 "T"
             ))
@@ -1194,16 +2032,225 @@ mod tests {
         );
     }
     #[test]
+    fn t_rel_() {
+        let t_00 = datetime_().easy_parse("+3d");
+        let t_01 = datetime_().easy_parse("+2wT9:");
+        let t_02 = datetime_().easy_parse("tomorrow");
+        let t_03 = datetime_().easy_parse("fri");
+        let t_04 = datetime_().easy_parse("eom");
+        let t_05 = datetime_().easy_parse("#12+1d");
+        let t_06 = datetime_().easy_parse("#12<d");
+        let t_10 = datetime_().easy_parse("+3");
+        let t_11 = datetime_().easy_parse("friday");
+        let t_12 = datetime_().easy_parse("#12+1");
+        let t_13 = datetime_().easy_parse("fridays");
+        let t_14 = datetime_().easy_parse("tues");
+        let t_15 = datetime_().easy_parse("wednesdayT9:");
+        let rel = |r, t| models::EasyDateTime {
+            date: None,
+            time: t,
+            rel: Some(r),
+        };
+        let nine = || {
+            Some(models::EasyTime {
+                h: Some(9),
+                m: None,
+            })
+        };
+        assert_eq!(t_00, Ok((rel(models::EasyRel::Days(3), None), "")));
+        assert_eq!(t_01, Ok((rel(models::EasyRel::Days(14), nine()), "")));
+        assert_eq!(t_02, Ok((rel(models::EasyRel::Days(1), None), "")));
+        assert_eq!(t_03, Ok((rel(models::EasyRel::Weekday(4), None), "")));
+        assert_eq!(t_04, Ok((rel(models::EasyRel::EndOfMonth, None), "")));
+        assert_eq!(t_05, Ok((rel(models::EasyRel::Task(12, 1), None), "")));
+        assert_eq!(t_06, Ok((rel(models::EasyRel::Task(12, 0), None), "<d")));
+        assert!(t_10.is_err());
+        assert_eq!(t_11, Ok((rel(models::EasyRel::Weekday(4), None), "")));
+        assert_eq!(t_12, Ok((rel(models::EasyRel::Task(12, 0), None), "+1")));
+        assert!(t_13.is_err());
+        assert!(t_14.is_err());
+        assert_eq!(t_15, Ok((rel(models::EasyRel::Weekday(2), nine()), "")));
+    }
+    #[test]
+    fn t_rel_complete() {
+        use chrono::{NaiveDate, TimeZone, Utc};
+
+        let tz = chrono_tz::Asia::Tokyo;
+        // Wednesday 2021/10/06T21:34 in Tokyo
+        let now = Utc.ymd(2021, 10, 6).and_hms(12, 34, 0);
+        let complete_from = |s: &str, week_start: u32| {
+            datetime_()
+                .easy_parse(s)
+                .unwrap()
+                .0
+                .complete(&tz, &now, week_start)
+                .unwrap()
+        };
+        let complete = |s: &str| complete_from(s, 0);
+        let at = |m, d, h, min| NaiveDate::from_ymd(2021, m, d).and_hms(h, min, 0);
+        assert_eq!(complete("+3d"), at(10, 9, 0, 0));
+        assert_eq!(complete("+2wT9:"), at(10, 20, 9, 0));
+        assert_eq!(complete("today"), at(10, 6, 0, 0));
+        assert_eq!(complete("tomorrowT:30"), at(10, 7, 21, 30));
+        assert_eq!(complete("fri"), at(10, 8, 0, 0));
+        assert_eq!(complete("wed"), at(10, 13, 0, 0));
+        assert_eq!(complete("tue"), at(10, 12, 0, 0));
+        assert_eq!(complete("eow"), at(10, 11, 0, 0));
+        // weeks starting on Sunday
+        assert_eq!(complete_from("eow", 6), at(10, 10, 0, 0));
+        assert_eq!(complete("eom"), at(11, 1, 0, 0));
+        // counted from the task's date, which is given as now
+        assert_eq!(complete("#12+1d"), at(10, 7, 21, 34));
+        assert_eq!(complete("#12+1wT9:"), at(10, 13, 9, 0));
+        let dec = Utc.ymd(2021, 12, 15).and_hms(0, 0, 0);
+        assert_eq!(
+            datetime_()
+                .easy_parse("eom")
+                .unwrap()
+                .0
+                .complete(&tz, &dec, 0),
+            Some(NaiveDate::from_ymd(2022, 1, 1).and_hms(0, 0, 0))
+        );
+    }
+    #[test]
+    fn t_globalize() {
+        use chrono::{DateTime, TimeZone, Utc};
+        use chrono_tz::Tz;
+        use models::Dst::*;
+
+        let user = |tz: Tz, now: DateTime<Utc>| models::AuthedUser {
+            id: 0,
+            tz: tz,
+            clock: models::Clock::Fixed(now),
+            settings: models::Settings::default(),
+        };
+        // the time read and whether it was noted as adjusted
+        let globalize = |user: &models::AuthedUser, s: &str, dst: models::Dst| {
+            let easy = datetime_().easy_parse(s).unwrap().0;
+            let user = models::AuthedUser {
+                id: user.id,
+                tz: user.tz,
+                clock: models::Clock::Fixed(user.clock.now()),
+                settings: models::Settings {
+                    dst: dst,
+                    ..Default::default()
+                },
+            };
+            user.globalize_from(&easy, &user.clock.now())
+                .ok()
+                .map(|(dt, note)| (dt, note.is_some()))
+        };
+        let utc = |y, m, d, h, min| Utc.ymd(y, m, d).and_hms(h, min, 0);
+
+        // New York springs forward at 2:00 on 2021/3/14 and falls back at 2:00 on 2021/11/7
+        let ny = chrono_tz::America::New_York;
+        let spring = user(ny, utc(2021, 3, 14, 6, 0)); // 1:00 EST
+        assert_eq!(
+            globalize(&spring, "1:30", Strict),
+            Some((utc(2021, 3, 14, 6, 30), false))
+        );
+        assert_eq!(
+            globalize(&spring, "3:30", Strict),
+            Some((utc(2021, 3, 14, 7, 30), false))
+        );
+        assert_eq!(
+            globalize(&spring, "2:30", Earlier),
+            Some((utc(2021, 3, 14, 7, 30), true))
+        );
+        assert_eq!(
+            globalize(&spring, "2:30", Later),
+            Some((utc(2021, 3, 14, 7, 30), true))
+        );
+        assert_eq!(globalize(&spring, "2:30", Strict), None);
+        assert_eq!(
+            globalize(&spring, "+1d", Strict),
+            Some((utc(2021, 3, 15, 4, 0), false))
+        );
+        let fall = user(ny, utc(2021, 11, 6, 16, 0)); // 12:00 EDT
+        assert_eq!(
+            globalize(&fall, "tomorrowT0:30", Strict),
+            Some((utc(2021, 11, 7, 4, 30), false))
+        );
+        assert_eq!(
+            globalize(&fall, "tomorrowT2:30", Strict),
+            Some((utc(2021, 11, 7, 7, 30), false))
+        );
+        assert_eq!(
+            globalize(&fall, "tomorrowT1:30", Earlier),
+            Some((utc(2021, 11, 7, 5, 30), true))
+        );
+        assert_eq!(
+            globalize(&fall, "tomorrowT1:30", Later),
+            Some((utc(2021, 11, 7, 6, 30), true))
+        );
+        assert_eq!(globalize(&fall, "tomorrowT1:30", Strict), None);
+        assert_eq!(
+            globalize(&fall, "//7T1:", Earlier),
+            Some((utc(2021, 11, 7, 5, 0), true))
+        );
+
+        // Sydney falls back at 3:00 on 2021/4/4 and springs forward at 2:00 on 2021/10/3
+        let sydney = user(chrono_tz::Australia::Sydney, utc(2021, 1, 1, 0, 0));
+        assert_eq!(
+            globalize(&sydney, "2021/4/4T2:30", Earlier),
+            Some((utc(2021, 4, 3, 15, 30), true))
+        );
+        assert_eq!(
+            globalize(&sydney, "2021/4/4T2:30", Later),
+            Some((utc(2021, 4, 3, 16, 30), true))
+        );
+        assert_eq!(
+            globalize(&sydney, "2021/10/3T2:30", Earlier),
+            Some((utc(2021, 10, 2, 16, 30), true))
+        );
+        assert_eq!(globalize(&sydney, "2021/10/3T2:30", Strict), None);
+        // Lord Howe Island shifts by half an hour, at 2:00 both ways
+        let lord_howe = user(chrono_tz::Australia::Lord_Howe, utc(2021, 1, 1, 0, 0));
+        assert_eq!(
+            globalize(&lord_howe, "2021/4/4T1:45", Earlier),
+            Some((utc(2021, 4, 3, 14, 45), true))
+        );
+        assert_eq!(
+            globalize(&lord_howe, "2021/4/4T1:45", Later),
+            Some((utc(2021, 4, 3, 15, 15), true))
+        );
+        assert_eq!(
+            globalize(&lord_howe, "2021/10/3T2:15", Earlier),
+            Some((utc(2021, 10, 2, 15, 45), true))
+        );
+        // Santiago springs forward at midnight, so the day itself starts at 1:00
+        let santiago = user(chrono_tz::America::Santiago, utc(2021, 1, 1, 0, 0));
+        assert_eq!(
+            globalize(&santiago, "2021/9/5", Earlier),
+            Some((utc(2021, 9, 5, 4, 0), true))
+        );
+        assert_eq!(
+            globalize(&santiago, "2021/9/5T1:", Strict),
+            Some((utc(2021, 9, 5, 4, 0), false))
+        );
+    }
+    #[test]
     fn t_req_task_() {
         std::env::set_var("INDENT", "    ");
         let t_01 = req_task_().easy_parse("title");
         let t_02 = req_task_().easy_parse("        title");
         let t_03 = req_task_().easy_parse("    title http://localhost");
         let t_04 = req_task_().easy_parse("    title\n    http://localhost");
+        let t_05 = req_task_().easy_parse(
+            "    title\n    http://localhost\n    | **bold**\n    |\n    || a | b |\nnext",
+        );
+        let t_06 = req_task_().easy_parse("title\n|no space");
+        let t_07 = req_task_().easy_parse(
+            "title\n[spec sheet] https://a.com/spec\nhttp://b.com\n    [] http://c.com",
+        );
         let t_10 = req_task_().easy_parse("");
         let t_11 = req_task_().easy_parse("      ambiguous indent");
         let t_13 = req_task_().easy_parse("    title\n    some    http://localhost");
         let t_14 = req_task_().easy_parse("    title\n    http://localhost    some");
+        let link = |label: Option<&str>, url: &str| models::Link {
+            label: label.map(String::from),
+            url: String::from(url),
+        };
         assert_eq!(
             t_01,
             Ok((
@@ -1218,9 +2265,11 @@ mod tests {
                         assign: None,
                         startable: None,
                         deadline: None,
+                        tags: Vec::new(),
                         title: String::from("title"),
                     },
-                    link: None,
+                    links: Vec::new(),
+                    notes: None,
                 },
                 ""
             ))
@@ -1239,9 +2288,11 @@ mod tests {
                         assign: None,
                         startable: None,
                         deadline: None,
+                        tags: Vec::new(),
                         title: String::from("title"),
                     },
-                    link: None,
+                    links: Vec::new(),
+                    notes: None,
                 },
                 ""
             ))
@@ -1260,9 +2311,11 @@ mod tests {
                         assign: None,
                         startable: None,
                         deadline: None,
+                        tags: Vec::new(),
                         title: String::from("title http://localhost"), // inline links fall into title
                     },
-                    link: None,
+                    links: Vec::new(),
+                    notes: None,
                 },
                 ""
             ))
@@ -1281,13 +2334,51 @@ mod tests {
                         assign: None,
                         startable: None,
                         deadline: None,
+                        tags: Vec::new(),
                         title: String::from("title"),
                     },
-                    link: Some(String::from("http://localhost")), // ok
+                    links: vec![link(None, "http://localhost")], // ok
+                    notes: None,
                 },
                 ""
             ))
         );
+        let task = |links: Vec<models::Link>, notes: Option<&str>| ReqTask {
+            indent: 0,
+            attribute: Attribute {
+                title: String::from("title"),
+                ..Default::default()
+            },
+            links: links,
+            notes: notes.map(String::from),
+        };
+        assert_eq!(
+            t_05,
+            Ok((
+                ReqTask {
+                    indent: 1,
+                    ..task(
+                        vec![link(None, "http://localhost")],
+                        Some("**bold**\n\n| a | b |")
+                    )
+                },
+                "next"
+            ))
+        );
+        assert_eq!(t_06, Ok((task(Vec::new(), Some("no space")), "")));
+        assert_eq!(
+            t_07,
+            Ok((
+                task(
+                    vec![
+                        link(Some("spec sheet"), "https://a.com/spec"),
+                        link(None, "http://b.com"),
+                    ],
+                    None
+                ),
+                "    [] http://c.com"
+            ))
+        );
         assert!(t_10.is_err());
         assert!(t_11.is_err());
         assert_eq!(
@@ -1304,9 +2395,11 @@ mod tests {
                         assign: None,
                         startable: None,
                         deadline: None,
+                        tags: Vec::new(),
                         title: String::from("title"),
                     },
-                    link: None,
+                    links: Vec::new(),
+                    notes: None,
                 },
                 "    some    http://localhost"
             ))
@@ -1330,8 +2423,9 @@ mod tests {
     #[test]
     fn t_attribute_() {
         let t_00 = attribute_().easy_parse("https://");
-        let t_02 = attribute_()
-            .easy_parse("#333 h] something * 15:- 魁 -/12/ [t0 [t1 $5 great $530000. @satun ⚡");
+        let t_02 = attribute_().easy_parse(
+            "#333 h] something * 15:- 魁 -/12/ [t0 [t1 $5 great $530000. @satun +wip ⚡ +a/b +wip",
+        );
         let t_03 = attribute_().easy_parse("//T: //T //: // T: T :");
         let t_04 = attribute_().easy_parse("//T- //:- T:- T-");
         let t_10 = attribute_().easy_parse("");
@@ -1347,6 +2441,8 @@ mod tests {
         let t_20 = attribute_().easy_parse("$");
         let t_21 = attribute_().easy_parse("@");
         let t_22 = attribute_().easy_parse("-T: -T");
+        let t_30 = attribute_().easy_parse("#12+1d- -fri lunch");
+        let t_31 = attribute_().easy_parse("#12 mon-fri");
         let mut attr = Attribute::default();
         assert_eq!(
             t_00,
@@ -1374,6 +2470,7 @@ mod tests {
                             h: Some(15),
                             m: None,
                         }),
+                        rel: None,
                     }),
                     deadline: Some(models::EasyDateTime {
                         date: Some(models::EasyDate {
@@ -1382,7 +2479,9 @@ mod tests {
                             d: None,
                         }),
                         time: None,
+                        rel: None,
                     }),
+                    tags: vec![String::from("wip"), String::from("a/b")],
                     title: String::from("something 魁 great ⚡"),
                 },
                 ""
@@ -1437,6 +2536,39 @@ mod tests {
         assert!(t_20.is_err());
         assert!(t_21.is_err());
         assert!(t_22.is_err());
+        let mut attr = Attribute::default();
+        assert_eq!(
+            t_30,
+            Ok((
+                {
+                    attr.startable = Some(models::EasyDateTime {
+                        date: None,
+                        time: None,
+                        rel: Some(models::EasyRel::Task(12, 1)),
+                    });
+                    attr.deadline = Some(models::EasyDateTime {
+                        date: None,
+                        time: None,
+                        rel: Some(models::EasyRel::Weekday(4)),
+                    });
+                    attr.title = String::from("lunch");
+                    attr
+                },
+                ""
+            ))
+        );
+        let mut attr = Attribute::default();
+        assert_eq!(
+            t_31,
+            Ok((
+                {
+                    attr.id = Some(12);
+                    attr.title = String::from("mon-fri");
+                    attr
+                },
+                ""
+            ))
+        );
     }
     #[test]
     fn t_link_() {
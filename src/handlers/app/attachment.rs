@@ -0,0 +1,369 @@
+use actix_web::http::header::{self, ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::errors;
+use crate::models;
+use crate::schema::attachments;
+use crate::utils;
+
+pub static DIR: Lazy<PathBuf> = Lazy::new(|| PathBuf::from(utils::env_var("ATTACHMENT_DIR")));
+
+/// Bytes of an attachment at most.
+pub static LIMIT: Lazy<usize> = Lazy::new(|| {
+    utils::env_var("ATTACHMENT_LIMIT")
+        .parse::<usize>()
+        .expect("ATTACHMENT_LIMIT must be bytes")
+});
+
+#[derive(Deserialize)]
+pub struct Q {
+    pub name: String,
+}
+
+#[derive(Insertable)]
+#[table_name = "attachments"]
+struct NewAttachment {
+    task: i32,
+    name: String,
+    content_type: String,
+    size: i32,
+    digest: String,
+    uploaded_by: i32,
+}
+
+pub async fn upload(
+    tid: web::Path<i32>,
+    q: web::Query<Q>,
+    body: web::Bytes,
+    req: HttpRequest,
+    user: models::AuthedUser,
+    pool: web::Data<models::Pool>,
+) -> Result<HttpResponse, errors::ServiceError> {
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+    let res_body = web::block(move || {
+        let conn = pool.get().unwrap();
+        let q = q.into_inner();
+        q.valid(&body, *LIMIT)?;
+        attach(
+            tid.into_inner(),
+            &q,
+            content_type,
+            &body,
+            &DIR,
+            &user,
+            &conn,
+        )
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(res_body))
+}
+
+pub async fn download(
+    path: web::Path<(i32, i32)>,
+    user: models::AuthedUser,
+    pool: web::Data<models::Pool>,
+) -> Result<HttpResponse, errors::ServiceError> {
+    let (attachment, content) = web::block(move || {
+        let conn = pool.get().unwrap();
+        let (tid, aid) = path.into_inner();
+        fetch(tid, aid, &DIR, &user, &conn)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(attachment.content_type)
+        // the uploader chose the type, so browsers should not guess another
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .set(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(attachment.name)],
+        })
+        .body(content))
+}
+
+impl Q {
+    fn valid(&self, content: &[u8], limit: usize) -> Result<(), errors::ServiceError> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err(errors::ServiceError::BadRequest("file name empty.".into()));
+        }
+        if content.is_empty() {
+            return Err(errors::ServiceError::BadRequest(format!(
+                "{}: file empty.",
+                name
+            )));
+        }
+        if limit < content.len() {
+            return Err(errors::ServiceError::BadRequest(format!(
+                "{}: file over {} bytes.",
+                name, limit
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Stores the content under `dir` and attaches it to a task the user may edit.
+fn attach(
+    tid: i32,
+    q: &Q,
+    content_type: String,
+    content: &[u8],
+    dir: &Path,
+    user: &models::AuthedUser,
+    conn: &models::Conn,
+) -> Result<models::ResAttachment, errors::ServiceError> {
+    use crate::schema::attachments::dsl::attachments;
+
+    let task = user.editable(tid, &user.permitted(true, conn)?, conn)?;
+    let digest = store(dir, content)?;
+    conn.transaction::<_, errors::ServiceError, _>(|| {
+        let new = diesel::insert_into(attachments)
+            .values(&NewAttachment {
+                task: tid,
+                name: q.name.trim().to_string(),
+                content_type: content_type,
+                size: content.len() as i32,
+                digest: digest,
+                uploaded_by: user.id,
+            })
+            .get_result::<models::Attachment>(conn)?;
+        user.audit(
+            "attachment.add",
+            task.assign,
+            Some(tid),
+            None,
+            Some(serde_json::json!({ "name": new.name, "size": new.size })),
+            conn,
+        )?;
+        Ok(models::ResAttachment::from(new))
+    })
+}
+
+/// An attachment and its content, on a task the user may view.
+fn fetch(
+    tid: i32,
+    aid: i32,
+    dir: &Path,
+    user: &models::AuthedUser,
+    conn: &models::Conn,
+) -> Result<(models::Attachment, Vec<u8>), errors::ServiceError> {
+    use crate::schema::attachments::dsl::{attachments, task};
+
+    user.visible(tid, &user.permitted(false, conn)?, conn)?;
+    let attachment = attachments
+        .find(&aid)
+        .filter(task.eq(&tid))
+        .first::<models::Attachment>(conn)
+        .map_err(|_| {
+            errors::ServiceError::BadRequest(format!("#{}: attachment not found.", tid))
+        })?;
+    let content = std::fs::read(dir.join(&attachment.digest))?;
+    Ok((attachment, content))
+}
+
+/// Attachments of a task, which the user should be permitted to view.
+pub fn of(
+    tid: i32,
+    conn: &models::Conn,
+) -> Result<Vec<models::ResAttachment>, errors::ServiceError> {
+    use crate::schema::attachments::dsl::{attachments, id, task};
+
+    Ok(attachments
+        .filter(task.eq(&tid))
+        .order(id)
+        .load::<models::Attachment>(conn)?
+        .into_iter()
+        .map(models::ResAttachment::from)
+        .collect())
+}
+
+/// Writes the content under its digest, over the same content if it is there already.
+///
+/// Writing anew refreshes the modified time, so that `sweep` leaves the file to the upload.
+fn store(dir: &Path, content: &[u8]) -> Result<String, errors::ServiceError> {
+    let digest = format!("{:x}", Sha256::digest(content));
+    std::fs::create_dir_all(dir)?;
+    // renamed into place so that a file under a digest is always complete
+    let tmp = dir.join(format!(".{}", uuid::Uuid::new_v4()));
+    std::fs::write(&tmp, content)?;
+    std::fs::rename(&tmp, dir.join(&digest))?;
+    Ok(digest)
+}
+
+/// Deletes files no attachment refers to, returning how many were deleted.
+///
+/// Files younger than an hour are kept, as their uploads may be in progress.
+pub fn sweep(
+    dir: &Path,
+    now: DateTime<Utc>,
+    conn: &models::Conn,
+) -> Result<usize, errors::ServiceError> {
+    use crate::schema::attachments::dsl::{attachments, digest};
+
+    if !dir.exists() {
+        return Ok(0);
+    }
+    let referred = attachments
+        .select(digest)
+        .distinct()
+        .load::<String>(conn)?
+        .into_iter()
+        .collect::<HashSet<String>>();
+    let mut count = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let modified = DateTime::<Utc>::from(entry.metadata()?.modified()?);
+        if !referred.contains(&name) && modified < now - Duration::hours(1) {
+            std::fs::remove_file(entry.path())?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing;
+    use std::time::SystemTime;
+
+    fn tmp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("attachments-{}", uuid::Uuid::new_v4()))
+    }
+    fn age(path: &Path, hours: i64) {
+        let then = SystemTime::from(Utc::now() - Duration::hours(hours));
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(then)
+            .unwrap();
+    }
+    fn modified(path: &Path) -> DateTime<Utc> {
+        DateTime::<Utc>::from(std::fs::metadata(path).unwrap().modified().unwrap())
+    }
+
+    #[test]
+    fn t_store() {
+        let dir = tmp_dir();
+        let digest = store(&dir, b"pon").unwrap();
+        assert_eq!(std::fs::read(dir.join(&digest)).unwrap(), b"pon");
+        age(&dir.join(&digest), 2);
+        // the same content is written anew, as fresh as its upload
+        assert_eq!(store(&dir, b"pon").unwrap(), digest);
+        assert!(Utc::now() - Duration::minutes(1) < modified(&dir.join(&digest)));
+        assert_ne!(store(&dir, b"cho").unwrap(), digest);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn t_valid() {
+        let q = |name: &str| Q { name: name.into() };
+        assert!(q("a.txt").valid(b"pon", 3).is_ok());
+        assert!(q("a.txt").valid(b"pon!", 3).is_err());
+        assert!(q("a.txt").valid(b"", 3).is_err());
+        assert!(q("  ").valid(b"pon", 3).is_err());
+    }
+    #[test]
+    fn t_attach_and_fetch() {
+        use crate::schema::audits::dsl::{action, actor, audits, new_values, task};
+        use crate::schema::permissions::dsl::permissions;
+        use crate::schema::tasks::dsl::{assign, id, tasks, title};
+
+        let pool = match testing::pool() {
+            Some(pool) => pool,
+            None => return,
+        };
+        let conn = pool.get().unwrap();
+        let dir = tmp_dir();
+        let owner = testing::user("attach_owner", &conn);
+        let viewer = testing::user("attach_viewer", &conn);
+        let stranger = testing::user("attach_stranger", &conn);
+        diesel::insert_into(permissions)
+            .values(&models::Permission {
+                subject: viewer.id,
+                object: owner.id,
+                edit: false,
+            })
+            .execute(&conn)
+            .unwrap();
+        let tid = diesel::insert_into(tasks)
+            .values((title.eq("attached"), assign.eq(owner.id)))
+            .returning(id)
+            .get_result::<i32>(&conn)
+            .unwrap();
+        let q = Q {
+            name: " a.txt ".into(),
+        };
+        let attach = |user| attach(tid, &q, "text/plain".into(), b"pon", &dir, user, &conn);
+        assert!(attach(&viewer).is_err());
+        assert!(attach(&stranger).is_err());
+        let aid = attach(&owner).unwrap().id;
+        let (attachment, content) = fetch(tid, aid, &dir, &viewer, &conn).unwrap();
+        assert_eq!(attachment.name, "a.txt");
+        assert_eq!(content, b"pon");
+        assert!(fetch(tid, aid, &dir, &stranger, &conn).is_err());
+        assert!(fetch(tid + 1, aid, &dir, &owner, &conn).is_err());
+        let audited = audits
+            .filter(action.eq("attachment.add"))
+            .filter(task.eq(tid))
+            .select((actor, new_values))
+            .load::<(Option<i32>, Option<serde_json::Value>)>(&conn)
+            .unwrap();
+        assert_eq!(
+            audited,
+            vec![(
+                Some(owner.id),
+                Some(serde_json::json!({ "name": "a.txt", "size": 3 }))
+            )]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn t_sweep() {
+        use crate::schema::tasks::dsl::{assign, id, tasks, title};
+
+        let pool = match testing::pool() {
+            Some(pool) => pool,
+            None => return,
+        };
+        let conn = pool.get().unwrap();
+        let dir = tmp_dir();
+        let user = testing::user("sweeper", &conn);
+        let tid = diesel::insert_into(tasks)
+            .values((title.eq("swept"), assign.eq(user.id)))
+            .returning(id)
+            .get_result::<i32>(&conn)
+            .unwrap();
+        let q = Q {
+            name: "kept".into(),
+        };
+        let kept = store(&dir, b"kept").unwrap();
+        attach(tid, &q, "text/plain".into(), b"kept", &dir, &user, &conn).unwrap();
+        let young = store(&dir, b"young").unwrap();
+        let old = store(&dir, b"old").unwrap();
+        age(&dir.join(&kept), 2);
+        age(&dir.join(&old), 2);
+        assert_eq!(sweep(&dir, Utc::now(), &conn).unwrap(), 1);
+        assert!(dir.join(&kept).exists());
+        assert!(dir.join(&young).exists());
+        assert!(!dir.join(&old).exists());
+        assert_eq!(sweep(&tmp_dir(), Utc::now(), &conn).unwrap(), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,338 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+use crate::errors;
+use crate::handlers::_email::{self, Email};
+use crate::models;
+use crate::schema::comments;
+
+/// Bytes of a comment at most.
+const LIMIT: usize = 4 * 1024;
+
+#[derive(Deserialize)]
+pub struct ReqBody {
+    pub body: String,
+}
+
+#[derive(Queryable, Serialize)]
+struct ResComment {
+    id: i32,
+    author: String,
+    body: String,
+    created_at: DateTime<Utc>,
+    edited_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable)]
+#[table_name = "comments"]
+struct NewComment {
+    task: i32,
+    author: i32,
+    body: String,
+    created_at: DateTime<Utc>,
+}
+
+pub async fn list(
+    tid: web::Path<i32>,
+    user: models::AuthedUser,
+    pool: web::Data<models::Pool>,
+) -> Result<HttpResponse, errors::ServiceError> {
+    let res_body = web::block(move || {
+        use crate::schema::comments::dsl::*;
+        use crate::schema::users::dsl::{name, users};
+
+        let conn = pool.get().unwrap();
+        let tid = tid.into_inner();
+        user.visible(tid, &user.permitted(false, &conn)?, &conn)?;
+        Ok(comments
+            .filter(task.eq(&tid))
+            .inner_join(users)
+            .select((id, name, body, created_at, edited_at))
+            .order(id)
+            .load::<ResComment>(&conn)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(res_body))
+}
+
+pub async fn add(
+    tid: web::Path<i32>,
+    req: web::Json<ReqBody>,
+    user: models::AuthedUser,
+    pool: web::Data<models::Pool>,
+) -> Result<HttpResponse, errors::ServiceError> {
+    let res_body = web::block(move || {
+        use crate::schema::comments::dsl::comments;
+
+        let conn = pool.get().unwrap();
+        let tid = tid.into_inner();
+        let req = req.into_inner();
+        req.valid()?;
+        let (res, emails) = conn.transaction::<_, errors::ServiceError, _>(|| {
+            let task = user.visible(tid, &user.permitted(false, &conn)?, &conn)?;
+            let new = diesel::insert_into(comments)
+                .values(&NewComment {
+                    task: tid,
+                    author: user.id,
+                    body: req.body.clone(),
+                    created_at: user.clock.now(),
+                })
+                .get_result::<models::Comment>(&conn)?;
+            user.audit(
+                "comment.add",
+                task.assign,
+                Some(tid),
+                None,
+                Some(serde_json::json!({ "body": new.body })),
+                &conn,
+            )?;
+            let emails = notify(&req.mentions(), &task, &new, &user, &conn)?;
+            Ok((find(new.id, &conn)?, emails))
+        })?;
+        send(emails);
+        Ok(res)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(res_body))
+}
+
+pub async fn edit(
+    path: web::Path<(i32, i32)>,
+    req: web::Json<ReqBody>,
+    user: models::AuthedUser,
+    pool: web::Data<models::Pool>,
+) -> Result<HttpResponse, errors::ServiceError> {
+    let res_body = web::block(move || {
+        use crate::schema::comments::dsl::{body, edited_at};
+
+        let conn = pool.get().unwrap();
+        let (tid, cid) = path.into_inner();
+        let req = req.into_inner();
+        req.valid()?;
+        let (res, emails) = conn.transaction::<_, errors::ServiceError, _>(|| {
+            let task = user.visible(tid, &user.permitted(false, &conn)?, &conn)?;
+            let old = own(tid, cid, &user, &conn)?;
+            let new = diesel::update(&old)
+                .set((body.eq(&req.body), edited_at.eq(user.clock.now())))
+                .get_result::<models::Comment>(&conn)?;
+            user.audit(
+                "comment.edit",
+                task.assign,
+                Some(tid),
+                Some(serde_json::json!({ "body": old.body })),
+                Some(serde_json::json!({ "body": new.body })),
+                &conn,
+            )?;
+            // those mentioned already have been notified
+            let mentioned = ReqBody { body: old.body }.mentions();
+            let mentions = req
+                .mentions()
+                .into_iter()
+                .filter(|name| !mentioned.contains(name))
+                .collect::<Vec<String>>();
+            let emails = notify(&mentions, &task, &new, &user, &conn)?;
+            Ok((find(new.id, &conn)?, emails))
+        })?;
+        send(emails);
+        Ok(res)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(res_body))
+}
+
+pub async fn delete(
+    path: web::Path<(i32, i32)>,
+    user: models::AuthedUser,
+    pool: web::Data<models::Pool>,
+) -> Result<HttpResponse, errors::ServiceError> {
+    let res_body = web::block(move || {
+        let conn = pool.get().unwrap();
+        let (tid, cid) = path.into_inner();
+        conn.transaction::<_, errors::ServiceError, _>(|| {
+            let task = user.visible(tid, &user.permitted(false, &conn)?, &conn)?;
+            let old = own(tid, cid, &user, &conn)?;
+            let res = find(old.id, &conn)?;
+            diesel::delete(&old).execute(&conn)?;
+            user.audit(
+                "comment.delete",
+                task.assign,
+                Some(tid),
+                Some(serde_json::json!({ "body": old.body })),
+                None,
+                &conn,
+            )?;
+            Ok(res)
+        })
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(res_body))
+}
+
+impl ReqBody {
+    fn valid(&self) -> Result<(), errors::ServiceError> {
+        if self.body.trim().is_empty() {
+            return Err(errors::ServiceError::BadRequest("comment empty.".into()));
+        }
+        if LIMIT < self.body.len() {
+            return Err(errors::ServiceError::BadRequest(format!(
+                "comment over {} bytes.",
+                LIMIT
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn find(cid: i32, conn: &models::Conn) -> Result<ResComment, errors::ServiceError> {
+    use crate::schema::comments::dsl::*;
+    use crate::schema::users::dsl::{name, users};
+
+    Ok(comments
+        .find(cid)
+        .inner_join(users)
+        .select((id, name, body, created_at, edited_at))
+        .first::<ResComment>(conn)?)
+}
+
+/// A comment of yours on the task.
+fn own(
+    tid: i32,
+    cid: i32,
+    user: &models::AuthedUser,
+    conn: &models::Conn,
+) -> Result<models::Comment, errors::ServiceError> {
+    use crate::schema::comments::dsl::{author, comments, task};
+
+    comments
+        .find(cid)
+        .filter(task.eq(&tid))
+        .filter(author.eq(&user.id))
+        .first::<models::Comment>(conn)
+        .map_err(|_| errors::ServiceError::BadRequest(format!("#{}: comment not found.", tid)))
+}
+
+/// Notifies the users mentioned, except the author and those who cannot view the task,
+/// returning the emails to send them once the notifications are saved.
+fn notify(
+    names: &[String],
+    task: &models::Task,
+    comment: &models::Comment,
+    user: &models::AuthedUser,
+    conn: &models::Conn,
+) -> Result<Vec<Email>, errors::ServiceError> {
+    use crate::schema::users::dsl::{id, name, users};
+
+    let mut emails = Vec::new();
+    if names.is_empty() {
+        return Ok(emails);
+    }
+    let author = users.find(user.id).select(name).first::<String>(conn)?;
+    for object in users
+        .filter(name.eq_any(names))
+        .filter(id.ne(&user.id))
+        .load::<models::User>(conn)?
+    {
+        let mentioned = models::AuthedUser {
+            id: object.id,
+            tz: Tz::UTC, // only permissions matter here
+            clock: user.clock,
+            settings: models::Settings::from(&object),
+        };
+        if mentioned
+            .visible(task.id, &mentioned.permitted(false, conn)?, conn)
+            .is_err()
+        {
+            continue;
+        }
+        user.notify(object.id, "comment.mention", Some(task.id), conn)?;
+        if std::env::var("MENTION_EMAIL").as_deref() == Ok("true") {
+            // a template error is logged, and leaves the notification in the app
+            if let Ok(email) = Email::try_from(_email::Mentioned {
+                author: author.clone(),
+                task: task.id,
+                title: task.title.clone(),
+                body: comment.body.clone(),
+                object: object,
+            }) {
+                emails.push(email);
+            }
+        }
+    }
+    Ok(emails)
+}
+
+/// Sends emails at best, logging failures: they are only copies of notifications saved.
+fn send(emails: Vec<Email>) {
+    for email in emails {
+        if let Err(e) = email.send() {
+            println!("Mention Email Error:\n{:#?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing;
+
+    #[test]
+    fn t_notify_those_who_view() {
+        use crate::schema::notifications::dsl as n;
+        use crate::schema::permissions::dsl::permissions;
+        use crate::schema::tasks::dsl::{assign, tasks, title};
+
+        let pool = match testing::pool() {
+            Some(pool) => pool,
+            None => return,
+        };
+        let conn = pool.get().unwrap();
+        let author = testing::user("mention_author", &conn);
+        let viewer = testing::user("mention_viewer", &conn);
+        let stranger = testing::user("mention_stranger", &conn);
+        diesel::insert_into(permissions)
+            .values(&models::Permission {
+                subject: viewer.id,
+                object: author.id,
+                edit: false,
+            })
+            .execute(&conn)
+            .unwrap();
+        let task = diesel::insert_into(tasks)
+            .values((title.eq("mentioned"), assign.eq(author.id)))
+            .get_result::<models::Task>(&conn)
+            .unwrap();
+        let comment = diesel::insert_into(comments::table)
+            .values(&NewComment {
+                task: task.id,
+                author: author.id,
+                body: "@mention_viewer @mention_stranger @mention_author".into(),
+                created_at: author.clock.now(),
+            })
+            .get_result::<models::Comment>(&conn)
+            .unwrap();
+        let names = ReqBody {
+            body: comment.body.clone(),
+        }
+        .mentions();
+        notify(&names, &task, &comment, &author, &conn).unwrap();
+        let notified = |user: &models::AuthedUser| {
+            n::notifications
+                .filter(n::owner.eq(user.id))
+                .filter(n::kind.eq("comment.mention"))
+                .count()
+                .get_result::<i64>(&conn)
+                .unwrap()
+        };
+        assert_eq!(notified(&viewer), 1);
+        assert_eq!(notified(&stranger), 0);
+        assert_eq!(notified(&author), 0);
+    }
+}
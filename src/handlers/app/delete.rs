@@ -9,11 +9,13 @@ use crate::models;
 pub struct ReqBody {
     tasks: Vec<i32>,
     token: Option<uuid::Uuid>,
+    cascade: Option<bool>,
 }
 
 #[derive(Serialize)]
 pub struct ResBody {
     token: Option<uuid::Uuid>,
+    tasks: Vec<i32>,
 }
 
 pub async fn delete(
@@ -22,26 +24,32 @@ pub async fn delete(
     pool: web::Data<models::Pool>,
 ) -> Result<HttpResponse, errors::ServiceError> {
     let res_body = web::block(move || {
-        use crate::schema::tasks::dsl::{id, tasks};
-
         let conn = pool.get().unwrap();
         let req = req.into_inner();
         match req.token {
             None => {
-                req.accept(&user, &conn)?;
-                // create one-time token
+                let targets = req.accept(&user, &conn)?;
+                // create one-time token for these targets alone
                 Ok(ResBody {
-                    token: Some(user.get_token(&conn)?.id),
+                    token: Some(user.get_token(targets.clone(), &conn)?.id),
+                    tasks: targets,
                 })
             },
             Some(token) => {
-                user.consume_token(token, &conn)?;
-                // perform deletion
-                diesel::delete(
-                    tasks.filter(id.eq_any(req.tasks))
-                ).execute(&conn)?;
-                Ok(ResBody {
-                    token: None,
+                conn.transaction(|| {
+                    let confirmed = user.consume_token(token, &conn)?.tasks;
+                    let targets = req.accept(&user, &conn)?;
+                    if targets != confirmed {
+                        return Err(errors::ServiceError::BadRequest(
+                            "tasks changed since confirmation, delete again.".into(),
+                        ));
+                    }
+                    // move to the trash
+                    super::trash::discard(&user, &targets, &conn)?;
+                    Ok(ResBody {
+                        token: None,
+                        tasks: targets,
+                    })
                 })
             },
         }
@@ -55,23 +63,28 @@ pub async fn delete(
 }
 
 impl ReqBody {
+    /// Every task to be deleted, checked for edit permission.
     fn accept(
         &self,
         user: &models::AuthedUser,
         conn: &models::Conn,
-    ) -> Result<(), errors::ServiceError> {
-        use crate::schema::tasks::dsl::{assign, id, tasks};
+    ) -> Result<Vec<i32>, errors::ServiceError> {
+        use crate::schema::arrows::dsl::arrows;
 
-        if let Some(tid) = tasks
-            .select(id)
-            .filter(id.eq_any(&self.tasks))
-            .filter(assign.ne(&user.id))
-            .first::<i32>(conn).ok() {
-                return Err(errors::ServiceError::BadRequest(format!(
-                    "#{}: not your item.",
-                    tid
-                )));
+        let mut targets = self.tasks.clone();
+        if self.cascade.unwrap_or_default() {
+            let _arrows: models::Arrows = arrows.load::<models::Arrow>(conn)?.into();
+            targets = targets
+                .iter()
+                .flat_map(|tid| models::Tid::from(*tid).nodes_to(models::LR::Leaf, &_arrows))
+                .collect();
+        }
+        targets.sort();
+        targets.dedup();
+        let permitted = user.permitted(true, conn)?;
+        for tid in &targets {
+            user.editable(*tid, &permitted, conn)?;
         }
-        Ok(())
+        Ok(targets)
     }
 }
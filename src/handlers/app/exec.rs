@@ -1,6 +1,7 @@
 use actix_web::{web, HttpResponse};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 
 use crate::errors;
 use crate::models;
@@ -23,46 +24,74 @@ pub async fn exec(
     pool: web::Data<models::Pool>,
 ) -> Result<HttpResponse, errors::ServiceError> {
     let res_body = web::block(move || {
-        use crate::schema::arrows::dsl::arrows;
-        use crate::schema::permissions::dsl::*;
-        use crate::schema::tasks::dsl::{assign, id, is_archived, tasks};
-        use diesel::dsl::exists;
+        use crate::schema::arrows::dsl::{arrows, source, target};
+        use crate::schema::tasks::dsl::{assign, deleted_at, id, is_archived, tasks};
 
         let conn = pool.get().unwrap();
         let req = req.into_inner();
-        let _arrows: models::Arrows = arrows.load::<models::Arrow>(&conn)?.into();
-        let entries = req.verify(&user, &conn)?;
-        let targets = entries
-            .iter()
-            .flat_map(|tid| {
-                models::Tid::from(*tid).nodes_to(
-                    if req.revert {
-                        models::LR::Root
-                    } else {
-                        models::LR::Leaf
-                    },
-                    &_arrows,
-                )
-            })
-            .collect::<Vec<i32>>();
+        conn.transaction(|| {
+            let _arrows: models::Arrows = arrows.load::<models::Arrow>(&conn)?.into();
+            let entries = req.verify(&user, &conn)?;
+            let permitted = user.permitted(true, &conn)?;
+            let targets = entries
+                .iter()
+                .flat_map(|tid| {
+                    models::Tid::from(*tid).nodes_to(
+                        if req.revert {
+                            models::LR::Root
+                        } else {
+                            models::LR::Leaf
+                        },
+                        &_arrows,
+                    )
+                })
+                .collect::<Vec<i32>>();
 
-        let count = diesel::update(
-            tasks
-                .filter(exists(
-                    permissions
-                        .filter(subject.eq(&user.id))
-                        .filter(object.eq(assign))
-                        .filter(edit),
-                ))
-                .filter(is_archived.eq(&req.revert))
-                .filter(id.eq_any(&targets)),
-        )
-        .set(is_archived.eq(&!req.revert))
-        .execute(&conn)?;
+            let updated = diesel::update(
+                tasks
+                    .filter(deleted_at.is_null())
+                    .filter(permitted.filter())
+                    .filter(is_archived.eq(&req.revert))
+                    .filter(id.eq_any(&targets)),
+            )
+            .set(is_archived.eq(&!req.revert))
+            .get_results::<models::Task>(&conn)?;
+            let action = if req.revert {
+                "task.revert"
+            } else {
+                "task.archive"
+            };
+            for t in &updated {
+                user.audit(
+                    action,
+                    t.assign,
+                    Some(t.id),
+                    Some(archived(req.revert)),
+                    Some(archived(!req.revert)),
+                    &conn,
+                )?;
+            }
+            if !req.revert {
+                let ids = updated.iter().map(|t| t.id).collect::<Vec<i32>>();
+                // let those whose items depend on the archived ones know
+                for (archived_, owner_) in arrows
+                    .inner_join(tasks.on(id.eq(target)))
+                    .filter(source.eq_any(&ids))
+                    .filter(is_archived.eq(false))
+                    .filter(deleted_at.is_null())
+                    .select((source, assign))
+                    .distinct()
+                    .load::<(i32, i32)>(&conn)?
+                {
+                    user.notify(owner_, "task.archive", Some(archived_), &conn)?;
+                }
+            }
+            let count = updated.len();
 
-        Ok(ResBody {
-            count: count,
-            chain: count - entries.len(),
+            Ok(ResBody {
+                count: count,
+                chain: count - entries.len(),
+            })
         })
     })
     .await?;
@@ -76,21 +105,12 @@ impl ReqBody {
         user: &models::AuthedUser,
         conn: &models::Conn,
     ) -> Result<Vec<i32>, errors::ServiceError> {
-        use crate::schema::permissions::dsl::*;
-        use crate::schema::tasks::dsl::{assign, id, is_archived, tasks};
-        use diesel::dsl::exists;
+        use crate::schema::tasks::dsl::{deleted_at, id, is_archived, tasks};
 
+        let permitted = user.permitted(true, conn)?;
         if let Some(tid) = tasks
             .filter(id.eq_any(&self.tasks))
-            .filter(
-                exists(
-                    permissions
-                        .filter(subject.eq(&user.id))
-                        .filter(object.eq(assign))
-                        .filter(edit),
-                )
-                .eq(false),
-            )
+            .filter(diesel::dsl::not(permitted.filter()))
             .select(id)
             .first::<i32>(conn)
             .ok()
@@ -101,9 +121,14 @@ impl ReqBody {
             )));
         }
         Ok(tasks
+            .filter(deleted_at.is_null())
             .filter(is_archived.eq(&self.revert))
             .filter(id.eq_any(&self.tasks))
             .select(id)
             .load::<i32>(conn)?)
     }
 }
+
+fn archived(is_archived: bool) -> serde_json::Value {
+    json!({ "is_archived": is_archived })
+}
@@ -7,6 +7,9 @@ use crate::models::{self, Selectable};
 
 #[derive(Serialize)]
 pub struct ResBody {
+    notes: Option<String>,
+    links: Vec<models::Link>,
+    attachments: Vec<models::ResAttachment>,
     pred: Vec<models::ResTask>,
     succ: Vec<models::ResTask>,
 }
@@ -18,37 +21,58 @@ pub async fn focus(
 ) -> Result<HttpResponse, errors::ServiceError> {
     let res_body = web::block(move || {
         use crate::schema::arrows::dsl::*;
-        use crate::schema::permissions::dsl::*;
-        use crate::schema::tasks::dsl::{assign, id, tasks};
+        use crate::schema::tasks::dsl::{deleted_at, id, tasks};
         use crate::schema::users::dsl::users;
         use diesel::dsl::exists;
 
         let conn = pool.get().unwrap();
         let tid = tid.into_inner();
-        let query = tasks
-            .filter(exists(
-                permissions
-                    .filter(subject.eq(&user.id))
-                    .filter(object.eq(assign)),
-            ))
-            .inner_join(users)
-            .select(models::SelTask::columns());
-
-        let pred = query
+        let permitted = user.permitted(false, &conn)?;
+        let visible = || {
+            tasks
+                .filter(deleted_at.is_null())
+                .filter(id.eq_any(tasks.filter(permitted.filter()).select(id).into_boxed()))
+        };
+        let query = || {
+            visible()
+                .inner_join(users)
+                .select(models::SelTask::columns())
+        };
+
+        let focused = visible()
+            .filter(id.eq(&tid))
+            .first::<models::Task>(&conn)
+            .optional()?;
+        let (notes_, links, attachments) = match focused {
+            Some(t) => (
+                t.notes.clone(),
+                t.links(&conn)?,
+                super::attachment::of(tid, &conn)?,
+            ),
+            None => (None, Vec::new(), Vec::new()),
+        };
+
+        let mut pred = query()
             .filter(exists(arrows.filter(source.eq(id)).filter(target.eq(&tid))))
             .load::<models::SelTask>(&conn)?
             .into_iter()
             .map(|t| t.to_res())
-            .collect();
+            .collect::<Vec<models::ResTask>>();
 
-        let succ = query
+        let mut succ = query()
             .filter(exists(arrows.filter(source.eq(&tid)).filter(target.eq(id))))
             .load::<models::SelTask>(&conn)?
             .into_iter()
             .map(|t| t.to_res())
-            .collect();
+            .collect::<Vec<models::ResTask>>();
+
+        models::ResTask::annotate(&mut pred, &conn)?;
+        models::ResTask::annotate(&mut succ, &conn)?;
 
         Ok(ResBody {
+            notes: notes_,
+            links: links,
+            attachments: attachments,
             pred: pred,
             succ: succ,
         })
@@ -0,0 +1,96 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+
+use crate::errors;
+use crate::models;
+
+#[derive(Serialize)]
+pub struct ResBody {
+    versions: Vec<ResVersion>,
+}
+
+#[derive(Serialize)]
+struct ResVersion {
+    id: i32,
+    title: String,
+    assign: String,
+    is_starred: bool,
+    startable: Option<DateTime<Utc>>,
+    deadline: Option<DateTime<Utc>>,
+    weight: Option<f32>,
+    link: Option<String>,
+    notes: Option<String>,
+    tags: Vec<String>,
+    updated_at: DateTime<Utc>,
+    replaced_at: DateTime<Utc>,
+}
+
+impl From<(models::TaskVersion, String)> for ResVersion {
+    fn from((version, assign): (models::TaskVersion, String)) -> Self {
+        Self {
+            tags: version.tags(),
+            id: version.id,
+            title: version.title,
+            assign: assign,
+            is_starred: version.is_starred,
+            startable: version.startable,
+            deadline: version.deadline,
+            weight: version.weight,
+            link: version.link,
+            notes: version.notes,
+            updated_at: version.updated_at,
+            replaced_at: version.replaced_at,
+        }
+    }
+}
+
+pub async fn history(
+    tid: web::Path<i32>,
+    user: models::AuthedUser,
+    pool: web::Data<models::Pool>,
+) -> Result<HttpResponse, errors::ServiceError> {
+    let res_body = web::block(move || {
+        let conn = pool.get().unwrap();
+        versions(tid.into_inner(), &user, &conn)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(res_body))
+}
+
+/// Versions of a task the user may view, the latest first.
+pub fn versions(
+    tid: i32,
+    user: &models::AuthedUser,
+    conn: &models::Conn,
+) -> Result<ResBody, errors::ServiceError> {
+    use crate::schema::task_versions::dsl::*;
+    use crate::schema::tasks::dsl::tasks;
+    use crate::schema::users::dsl::{name, users};
+
+    let permitted = user.permitted(false, conn)?;
+    if tasks
+        .find(&tid)
+        .filter(permitted.filter())
+        .first::<models::Task>(conn)
+        .is_err()
+    {
+        return Err(errors::ServiceError::BadRequest(format!(
+            "#{}: item not found.",
+            tid
+        )));
+    }
+    let versions = task_versions
+        .filter(task.eq(&tid))
+        .inner_join(users)
+        .select((crate::schema::task_versions::all_columns, name))
+        .order(id.desc())
+        .load::<(models::TaskVersion, String)>(conn)?
+        .into_iter()
+        .map(ResVersion::from)
+        .collect();
+
+    Ok(ResBody { versions: versions })
+}
@@ -1,5 +1,5 @@
 use actix_web::{web, HttpResponse};
-use chrono::{Date, DateTime, Duration, NaiveDateTime, Utc};
+use chrono::{Date, DateTime, Duration, NaiveDateTime, TimeZone, Utc};
 use chrono_tz::Tz;
 use diesel::prelude::*;
 use gcollections::ops::{Bounded, Cardinality, Intersection};
@@ -7,19 +7,21 @@ use interval::interval_set::IntervalSet;
 use interval::interval_set::ToIntervalSet;
 use serde::{Deserialize, Serialize};
 use std::cmp::{max, min};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
+use super::_page::{Page, ResPage, Sort};
 use crate::errors;
 use crate::models::{self, Selectable};
 
 #[derive(Deserialize, Serialize)]
 pub struct Q {
     pub option: Option<String>,
-}
-
-#[derive(Serialize)]
-struct ResBody {
-    tasks: Vec<models::ResTask>,
+    pub search: Option<String>,
+    pub owner: Option<String>,
+    pub sort: Option<Sort>,
+    pub desc: Option<bool>,
+    pub after: Option<String>,
+    pub limit: Option<i64>,
 }
 
 pub async fn home(
@@ -29,32 +31,90 @@ pub async fn home(
 ) -> Result<HttpResponse, errors::ServiceError> {
     let res_body = web::block(move || {
         let conn = pool.get().unwrap();
-        let res_tasks = q.into_inner().config().query(&user, &conn)?;
-
-        Ok(ResBody { tasks: res_tasks })
+        let q = q.into_inner();
+        match q.config(&user) {
+            Config::Archives => Config::archives(&user, &q.page(), &conn),
+            Config::Search(name, owner) => {
+                user.find_search(&name, owner.as_deref(), &conn)?
+                    .run(&q.page(), &user, &conn)
+            }
+            config => {
+                let res_tasks = config.query(&user, &conn)?;
+                Ok(ResPage {
+                    total: res_tasks.len() as i64,
+                    next: None,
+                    buckets: buckets(&res_tasks, &user),
+                    tasks: res_tasks,
+                })
+            }
+        }
     })
     .await?;
 
     Ok(HttpResponse::Ok().json(res_body))
 }
 
+/// Groups scheduled tasks by the period of the timescale they start in, keeping their order.
+fn buckets(tasks: &[models::ResTask], user: &models::AuthedUser) -> Vec<models::Bucket> {
+    let mut map = BTreeMap::<NaiveDateTime, Vec<i32>>::new();
+    for t in tasks {
+        if let Some(schedule) = &t.schedule {
+            let local = schedule.l.with_timezone(&user.tz).naive_local();
+            let start = user
+                .settings
+                .timescale
+                .floor(local, user.settings.week_start);
+            map.entry(start).or_default().push(t.id);
+        }
+    }
+    map.into_iter()
+        .map(|(start, ids)| models::Bucket {
+            start: user
+                .tz
+                .from_local_datetime(&start)
+                .earliest()
+                .map(|dt| dt.with_timezone(&Utc))
+                .unwrap_or_else(|| DateTime::<Utc>::from_utc(start, Utc)),
+            tasks: ids,
+        })
+        .collect()
+}
+
 #[derive(Serialize, Eq, PartialEq)]
 pub enum Config {
     Home,
     Leaves,
     Roots,
     Archives,
+    Search(String, Option<String>),
 }
 
 impl Q {
-    fn config(&self) -> Config {
-        match self.option.as_deref() {
-            Some("archives") => Config::Archives,
-            Some("roots") => Config::Roots,
-            Some("leaves") => Config::Leaves,
+    /// Without an option or a search, the default view of the user is taken.
+    fn config(&self, user: &models::AuthedUser) -> Config {
+        if let Some(name) = &self.search {
+            return Config::Search(name.clone(), self.owner.clone());
+        }
+        match self
+            .option
+            .as_deref()
+            .unwrap_or(&user.settings.default_view)
+        {
+            "archives" => Config::Archives,
+            "roots" => Config::Roots,
+            "leaves" => Config::Leaves,
+            "home" => Config::Home,
+            name if self.option.is_none() => Config::Search(name.into(), None),
             _ => Config::Home,
         }
     }
+    fn page(&self) -> Page {
+        Page {
+            sort: self.sort.map(|sort| (sort, self.desc.unwrap_or_default())),
+            after: self.after.clone(),
+            limit: self.limit,
+        }
+    }
 }
 
 impl Config {
@@ -64,24 +124,25 @@ impl Config {
         conn: &models::Conn,
     ) -> Result<Vec<models::ResTask>, errors::ServiceError> {
         use crate::schema::allocations::dsl::{allocations, owner};
-        use crate::schema::tasks::dsl::{assign, is_archived, is_starred, tasks, updated_at};
+        use crate::schema::tasks::dsl::{assign, deleted_at, is_archived, tasks, updated_at};
         use crate::schema::users::dsl::users;
 
-        let is_archives = *self == Self::Archives;
+        match self {
+            Self::Archives => return Ok(Self::archives(user, &Page::default(), conn)?.tasks),
+            Self::Search(name, owner_) => {
+                return Ok(user
+                    .find_search(name, owner_.as_deref(), conn)?
+                    .run(&Page::default(), user, conn)?
+                    .tasks)
+            }
+            _ => (),
+        }
         let _intermediate = tasks
             .filter(assign.eq(&user.id))
-            .filter(is_archived.eq(&is_archives))
+            .filter(is_archived.eq(false))
+            .filter(deleted_at.is_null())
             .inner_join(users)
             .select(models::SelTask::columns());
-        if is_archives {
-            return Ok(_intermediate
-                .order((is_starred.desc(), updated_at.desc()))
-                .limit(100)
-                .load::<models::SelTask>(conn)?
-                .into_iter()
-                .map(|t| t.to_res())
-                .collect());
-        }
         let mut res_tasks = _intermediate
             .order(updated_at.desc())
             .load::<models::SelTask>(conn)?
@@ -95,13 +156,43 @@ impl Config {
             .load::<models::Allocation>(conn)?;
         let sorter = Sorter {
             allocations: _allocations,
-            now: Utc::now(),
+            now: user.clock.now(),
             tz: user.tz,
+            timescale: user.settings.timescale.rounding(),
+            week_start: user.settings.week_start,
         };
         sorter.exec(&mut res_tasks, arrows.clone());
         self.filter(&mut res_tasks, &arrows);
+        models::ResTask::annotate(&mut res_tasks, conn)?;
         Ok(res_tasks)
     }
+    /// Archived tasks are not scheduled, but paged instead.
+    pub fn archives(
+        user: &models::AuthedUser,
+        page: &Page,
+        conn: &models::Conn,
+    ) -> Result<ResPage, errors::ServiceError> {
+        use crate::schema::tasks::dsl::{assign, deleted_at, is_archived, tasks};
+        use crate::schema::users::dsl::users;
+
+        let query = || {
+            tasks
+                .filter(assign.eq(&user.id))
+                .filter(is_archived.eq(true))
+                .filter(deleted_at.is_null())
+                .inner_join(users)
+                .into_boxed()
+        };
+        let total = query().count().get_result::<i64>(conn)?;
+        let mut res_tasks = page
+            .apply(query().select(models::SelTask::columns()), None, user, conn)?
+            .load::<models::SelTask>(conn)?
+            .into_iter()
+            .map(|t| t.to_res())
+            .collect::<Vec<models::ResTask>>();
+        models::ResTask::annotate(&mut res_tasks, conn)?;
+        Ok(page.cut(res_tasks, total))
+    }
     fn filter(&self, tasks: &mut Vec<models::ResTask>, arrows: &models::Arrows) {
         match self {
             Self::Leaves => tasks.retain(|t| models::Tid::from(t.id).is(models::LR::Leaf, arrows)),
@@ -115,6 +206,8 @@ struct Sorter {
     allocations: Vec<models::Allocation>,
     now: DateTime<Utc>,
     tz: Tz,
+    timescale: models::Timescale, // schedules are rounded to
+    week_start: u32,
 }
 
 impl Sorter {
@@ -131,10 +224,8 @@ impl Sorter {
             // set schedule
             for t in tasks.iter_mut() {
                 if let (Some(l), Some(r)) = (sub.map[&t.id].startable, sub.map[&t.id].deadline) {
-                    t.schedule = Some(models::Schedule {
-                        l: self.unsplice(l).unwrap(),
-                        r: self.unsplice(r).unwrap(),
-                    })
+                    t.schedule =
+                        Some(self.schedule(self.unsplice(l).unwrap(), self.unsplice(r).unwrap(), t))
                 }
             }
         }
@@ -197,6 +288,35 @@ impl Sorter {
         };
         self.daily() * days + adjust
     }
+    /// Rounds the schedule to the timescale, yet neither before now or the startable
+    /// nor after the deadline of the task; it is left as is if nothing would remain.
+    fn schedule(
+        &self,
+        l: DateTime<Utc>,
+        r: DateTime<Utc>,
+        task: &models::ResTask,
+    ) -> models::Schedule {
+        let earliest = task.startable.map_or(self.now, |s| max(s, self.now));
+        let l_ = max(self.round(l), earliest);
+        let r_ = task
+            .deadline
+            .map_or(self.round(r), |d| min(self.round(r), d));
+        if l_ < r_ {
+            models::Schedule { l: l_, r: r_ }
+        } else {
+            models::Schedule { l: l, r: r }
+        }
+    }
+    /// Rounds to the nearest boundary of the timescale in local time.
+    fn round(&self, dt: DateTime<Utc>) -> DateTime<Utc> {
+        let local = dt.with_timezone(&self.tz).naive_local();
+        let rounded = self.timescale.nearest(local, self.week_start);
+        self.tz
+            .from_local_datetime(&rounded)
+            .earliest()
+            .map(|dt| dt.with_timezone(&Utc))
+            .unwrap_or(dt)
+    }
     fn unsplice(&self, dt: i64) -> Option<DateTime<Utc>> {
         let daily = self.daily();
         if daily == 0 {
@@ -355,4 +475,123 @@ mod tests {
             }
         );
     }
+    #[test]
+    fn t_timescale() {
+        use chrono::NaiveDate;
+        use models::Timescale::*;
+
+        let at = |m, d, h, min, s| NaiveDate::from_ymd(2021, m, d).and_hms(h, min, s);
+        // Wednesday
+        let now = at(10, 6, 21, 34, 56);
+        let new_year = NaiveDate::from_ymd(2022, 1, 1).and_hms(0, 0, 0);
+        assert_eq!(Year.floor(now, 0), at(1, 1, 0, 0, 0));
+        assert_eq!(Year.ceil(now, 0), new_year);
+        assert_eq!(Quarter.floor(now, 0), at(10, 1, 0, 0, 0));
+        assert_eq!(Quarter.ceil(now, 0), new_year);
+        assert_eq!(Month.ceil(now, 0), at(11, 1, 0, 0, 0));
+        assert_eq!(Week.floor(now, 0), at(10, 4, 0, 0, 0));
+        assert_eq!(Week.floor(now, 6), at(10, 3, 0, 0, 0));
+        assert_eq!(Week.ceil(now, 2), at(10, 13, 0, 0, 0));
+        assert_eq!(Day.ceil(now, 0), at(10, 7, 0, 0, 0));
+        assert_eq!(Hours.floor(now, 0), at(10, 6, 18, 0, 0));
+        assert_eq!(Hours.ceil(now, 0), at(10, 7, 0, 0, 0));
+        assert_eq!(Minutes.floor(now, 0), at(10, 6, 21, 30, 0));
+        assert_eq!(Minutes.ceil(now, 0), at(10, 6, 21, 45, 0));
+        assert_eq!(Second.ceil(now, 0), now);
+        assert_eq!(Hours.nearest(now, 0), at(10, 7, 0, 0, 0));
+        assert_eq!(Minutes.nearest(now, 0), at(10, 6, 21, 30, 0));
+        assert_eq!(Hours.nearest(at(10, 6, 3, 0, 0), 0), at(10, 6, 6, 0, 0));
+        assert_eq!(Hour.ceil(at(10, 6, 21, 0, 0), 0), at(10, 6, 21, 0, 0));
+        assert_eq!(Month.rounding(), Day);
+        assert_eq!(Minutes.rounding(), Minutes);
+    }
+    #[test]
+    fn t_round_and_buckets() {
+        use chrono::TimeZone;
+
+        let tz = chrono_tz::Asia::Tokyo;
+        let utc = |d, h, min| Utc.ymd(2021, 10, d).and_hms(h, min, 0);
+        let sorter = Sorter {
+            allocations: Vec::new(),
+            now: utc(6, 0, 0),
+            tz: tz,
+            timescale: models::Timescale::Hours,
+            week_start: 0,
+        };
+        // 2021/10/06T13:34 in Tokyo
+        assert_eq!(sorter.round(utc(6, 4, 34)), utc(6, 3, 0));
+        assert_eq!(sorter.round(utc(6, 6, 0)), utc(6, 9, 0));
+        let schedule = |l, r, startable, deadline| {
+            let task = models::ResTask {
+                startable: startable,
+                deadline: deadline,
+                ..Default::default()
+            };
+            let s = sorter.schedule(l, r, &task);
+            (s.l, s.r)
+        };
+        // 10:00-11:00 in Tokyo would vanish, so it stays
+        assert_eq!(
+            schedule(utc(6, 1, 0), utc(6, 2, 0), None, None),
+            (utc(6, 1, 0), utc(6, 2, 0))
+        );
+        // 13:34-20:00 becomes 12:00-18:00, but not before the startable 13:00
+        assert_eq!(
+            schedule(utc(6, 4, 34), utc(6, 11, 0), None, None),
+            (utc(6, 3, 0), utc(6, 9, 0))
+        );
+        assert_eq!(
+            schedule(utc(6, 4, 34), utc(6, 11, 0), Some(utc(6, 4, 0)), None),
+            (utc(6, 4, 0), utc(6, 9, 0))
+        );
+        // nor after the deadline 17:00
+        assert_eq!(
+            schedule(utc(6, 4, 34), utc(6, 11, 0), None, Some(utc(6, 8, 0))),
+            (utc(6, 3, 0), utc(6, 8, 0))
+        );
+        // nor before now 13:00
+        let later = Sorter {
+            allocations: Vec::new(),
+            now: utc(6, 4, 0),
+            tz: tz,
+            timescale: models::Timescale::Hours,
+            week_start: 0,
+        };
+        let s = later.schedule(utc(6, 4, 34), utc(6, 11, 0), &Default::default());
+        assert_eq!((s.l, s.r), (utc(6, 4, 0), utc(6, 9, 0)));
+
+        let user = models::AuthedUser {
+            id: 0,
+            tz: tz,
+            clock: models::Clock::Fixed(utc(6, 0, 0)),
+            settings: models::Settings {
+                timescale: models::Timescale::Day,
+                ..Default::default()
+            },
+        };
+        let task = |id, l: Option<DateTime<Utc>>| models::ResTask {
+            id: id,
+            schedule: l.map(|l| models::Schedule { l: l, r: l }),
+            ..Default::default()
+        };
+        let tasks = vec![
+            task(1, Some(utc(6, 15, 0))), // 2021/10/07T00:00 in Tokyo
+            task(2, Some(utc(6, 14, 59))),
+            task(3, None),
+            task(4, Some(utc(6, 3, 0))),
+        ];
+        assert_eq!(
+            buckets(&tasks, &user),
+            vec![
+                models::Bucket {
+                    start: utc(5, 15, 0),
+                    tasks: vec![2, 4],
+                },
+                models::Bucket {
+                    start: utc(6, 15, 0),
+                    tasks: vec![1],
+                },
+            ]
+        );
+    }
 }
@@ -0,0 +1,57 @@
+use actix_web::{web, HttpResponse};
+use diesel::prelude::*;
+use serde::Serialize;
+
+use crate::errors;
+use crate::jobs;
+use crate::models;
+
+#[derive(Serialize)]
+struct ResBody {
+    jobs: Vec<jobs::Status>,
+}
+
+/// Job status including the last errors, for administrators only.
+pub async fn jobs(
+    user: models::AuthedUser,
+    pool: web::Data<models::Pool>,
+) -> Result<HttpResponse, errors::ServiceError> {
+    web::block(move || {
+        use crate::schema::users::dsl::{email, users};
+
+        let conn = pool.get().unwrap();
+        let email_ = users.find(user.id).select(email).first::<String>(&conn)?;
+        if is_admin(&email_, &std::env::var("ADMIN_EMAILS").unwrap_or_default()) {
+            Ok(())
+        } else {
+            Err(errors::ServiceError::Unauthorized)
+        }
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ResBody {
+        jobs: jobs::status(),
+    }))
+}
+
+/// Whether the email is among the comma-separated `admins`.
+fn is_admin(email: &str, admins: &str) -> bool {
+    admins
+        .split(',')
+        .map(str::trim)
+        .any(|admin| !admin.is_empty() && admin == email)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_is_admin() {
+        assert!(is_admin("a@example.com", "a@example.com"));
+        assert!(is_admin("b@example.com", "a@example.com, b@example.com"));
+        assert!(!is_admin("c@example.com", "a@example.com,b@example.com"));
+        assert!(!is_admin("", ""));
+        assert!(!is_admin("a@example.com", ""));
+    }
+}
@@ -0,0 +1,192 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::errors;
+use crate::models;
+
+#[derive(Deserialize)]
+pub struct ReqBody {
+    notifications: Option<Vec<i32>>, // all of them unless specified
+}
+
+#[derive(Serialize)]
+pub struct ResBody {
+    notifications: Vec<ResNotification>,
+    unread: i64,
+}
+
+#[derive(Serialize)]
+struct ResNotification {
+    id: i32,
+    actor: Option<String>,
+    kind: String,
+    task: Option<i32>,
+    title: Option<String>,
+    created_at: DateTime<Utc>,
+    read_at: Option<DateTime<Utc>>,
+}
+
+pub async fn notifications(
+    user: models::AuthedUser,
+    pool: web::Data<models::Pool>,
+) -> Result<HttpResponse, errors::ServiceError> {
+    let res_body = web::block(move || {
+        let conn = pool.get().unwrap();
+        list(&user, &conn)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(res_body))
+}
+
+pub async fn read(
+    req: web::Json<ReqBody>,
+    user: models::AuthedUser,
+    pool: web::Data<models::Pool>,
+) -> Result<HttpResponse, errors::ServiceError> {
+    let res_body = web::block(move || {
+        use crate::schema::notifications::dsl::{id, notifications, owner, read_at};
+
+        let conn = pool.get().unwrap();
+        let unread = notifications
+            .filter(owner.eq(&user.id))
+            .filter(read_at.is_null());
+        match req.into_inner().notifications {
+            None => diesel::update(unread)
+                .set(read_at.eq(user.clock.now()))
+                .execute(&conn)?,
+            Some(ids) => diesel::update(unread.filter(id.eq_any(ids)))
+                .set(read_at.eq(user.clock.now()))
+                .execute(&conn)?,
+        };
+        list(&user, &conn)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(res_body))
+}
+
+/// The latest notifications for the user, read or not.
+/// Titles are given only of the tasks the user may still view.
+fn list(user: &models::AuthedUser, conn: &models::Conn) -> Result<ResBody, errors::ServiceError> {
+    use crate::schema::notifications::dsl::*;
+    use crate::schema::tasks::dsl::{deleted_at, id as task_id, tasks, title};
+    use crate::schema::users::dsl::{id as user_id, name, users};
+
+    let loaded = notifications
+        .filter(owner.eq(&user.id))
+        .left_join(users.on(user_id.nullable().eq(actor)))
+        .select((id, name.nullable(), kind, task, created_at, read_at))
+        .order(id.desc())
+        .limit(100)
+        .load::<(
+            i32,
+            Option<String>,
+            String,
+            Option<i32>,
+            DateTime<Utc>,
+            Option<DateTime<Utc>>,
+        )>(conn)?;
+    let titles = tasks
+        .filter(task_id.eq_any(loaded.iter().filter_map(|n| n.3).collect::<Vec<i32>>()))
+        .filter(deleted_at.is_null())
+        .filter(user.permitted(false, conn)?.filter())
+        .select((task_id, title))
+        .load::<(i32, String)>(conn)?
+        .into_iter()
+        .collect::<HashMap<i32, String>>();
+    let res = loaded
+        .into_iter()
+        .map(
+            |(id_, actor_, kind_, task_, created_at_, read_at_)| ResNotification {
+                id: id_,
+                actor: actor_,
+                kind: kind_,
+                task: task_,
+                title: task_.and_then(|tid| titles.get(&tid).cloned()),
+                created_at: created_at_,
+                read_at: read_at_,
+            },
+        )
+        .collect();
+    Ok(ResBody {
+        notifications: res,
+        unread: unread(user, conn)?,
+    })
+}
+
+pub fn unread(user: &models::AuthedUser, conn: &models::Conn) -> Result<i64, errors::DbError> {
+    use crate::schema::notifications::dsl::{notifications, owner, read_at};
+
+    notifications
+        .filter(owner.eq(&user.id))
+        .filter(read_at.is_null())
+        .count()
+        .get_result::<i64>(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing;
+
+    #[test]
+    fn t_list_hides_titles() {
+        use crate::schema::permissions::dsl::{object, permissions, subject};
+        use crate::schema::tasks::dsl::{assign, deleted_at, id, tasks, title};
+
+        let pool = match testing::pool() {
+            Some(pool) => pool,
+            None => return,
+        };
+        let conn = pool.get().unwrap();
+        let actor = testing::user("notifying_actor", &conn);
+        let owner = testing::user("notified_owner", &conn);
+        diesel::insert_into(permissions)
+            .values(&models::Permission {
+                subject: owner.id,
+                object: actor.id,
+                edit: false,
+            })
+            .execute(&conn)
+            .unwrap();
+        let insert = |title_: &str| {
+            diesel::insert_into(tasks)
+                .values((title.eq(title_), assign.eq(actor.id)))
+                .returning(id)
+                .get_result::<i32>(&conn)
+                .unwrap()
+        };
+        let (kept, trashed) = (insert("kept"), insert("trashed"));
+        actor
+            .notify(owner.id, "comment.mention", Some(kept), &conn)
+            .unwrap();
+        actor
+            .notify(owner.id, "comment.mention", Some(trashed), &conn)
+            .unwrap();
+        diesel::update(tasks.find(trashed))
+            .set(deleted_at.eq(Some(actor.clock.now())))
+            .execute(&conn)
+            .unwrap();
+        let titles = || {
+            list(&owner, &conn)
+                .unwrap()
+                .notifications
+                .into_iter()
+                .map(|n| n.title)
+                .collect::<Vec<Option<String>>>()
+        };
+        assert_eq!(titles(), vec![None, Some("kept".into())]);
+        diesel::delete(
+            permissions
+                .filter(subject.eq(owner.id))
+                .filter(object.eq(actor.id)),
+        )
+        .execute(&conn)
+        .unwrap();
+        assert_eq!(titles(), vec![None, None]);
+    }
+}
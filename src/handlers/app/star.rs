@@ -11,12 +11,15 @@ pub async fn star(
 ) -> Result<HttpResponse, errors::ServiceError> {
     let _ = web::block(move || {
         use crate::schema::permissions::dsl::*;
-        use crate::schema::tasks::dsl::{is_starred, tasks};
+        use crate::schema::tasks::dsl::{deleted_at, is_starred, tasks};
         use diesel::dsl::{exists, select};
 
         let conn = pool.get().unwrap();
         let tid = tid.into_inner();
-        let task = tasks.find(&tid).first::<models::Task>(&conn)?;
+        let task = tasks
+            .find(&tid)
+            .filter(deleted_at.is_null())
+            .first::<models::Task>(&conn)?;
         if select(exists(
             permissions
                 .filter(subject.eq(&user.id))
@@ -24,11 +27,14 @@ pub async fn star(
                 .filter(edit),
         ))
         .get_result(&conn)?
+            || user.permitted(true, &conn)?.covers(&task)
         {
-            diesel::update(&models::Tid::from(tid))
-                .set(is_starred.eq(&!task.is_starred))
-                .execute(&conn)?;
-            return Ok(());
+            return conn.transaction(|| {
+                let new = diesel::update(&task)
+                    .set(is_starred.eq(&!task.is_starred))
+                    .get_result::<models::Task>(&conn)?;
+                user.audit_task("task.star", Some(&task), Some(&new), &conn)
+            });
         }
         Err(errors::ServiceError::BadRequest(
             "no edit permission.".into(),
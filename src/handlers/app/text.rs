@@ -2,15 +2,23 @@ use actix_web::{web, HttpResponse};
 use chrono::{DateTime, NaiveTime, Utc};
 use chrono_tz::Tz;
 use diesel::prelude::*;
-use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::cmp::max;
+use std::collections::HashMap;
+use std::convert::TryFrom;
 
+use super::_page::{Page, ResPage};
 use crate::errors;
+use crate::handlers::_email::{self, Email};
 use crate::models::{self, Selectable};
-use crate::schema::{tasks, users};
+use crate::schema::{searches, tags, task_tags, tasks, teams, users};
 use crate::utils;
 
+/// Bytes of notes on a task.
+const NOTES_LIMIT: usize = 16 * 1024;
+/// Bytes of a text request, which may carry notes on several tasks.
+pub const BODY_LIMIT: usize = 256 * 1024;
+
 #[derive(Deserialize)]
 pub struct ReqBody {
     pub text: String,
@@ -19,7 +27,11 @@ pub struct ReqBody {
 #[derive(Serialize)]
 enum ResBody {
     Cmd(ResCmd),
-    Tasks { created: i32, updated: i32 },
+    Tasks {
+        created: i32,
+        updated: i32,
+        warnings: Vec<String>,
+    },
 }
 
 pub async fn text(
@@ -31,19 +43,39 @@ pub async fn text(
 
     let res_body = web::block(move || {
         let conn = pool.get().unwrap();
-        match req {
-            Req::Cmd(cmd) => Ok(ResBody::Cmd(match cmd {
-                // TODO /alias
-                ReqCmd::Help => ResCmd::Help(cmd_help("root.md")?),
-                ReqCmd::User(req) => ResCmd::User(req.handle(&user, &conn)?),
-                ReqCmd::Search(req) => ResCmd::Search(req.handle(&user, &conn)?),
-                ReqCmd::Tutorial => ResCmd::Tutorial(cmd_help("tutorial.md")?),
-                ReqCmd::Coffee => {
-                    return Err(errors::ServiceError::BadRequest("I'm a teapot.".into()))
-                }
-            })),
-            Req::Tasks(tasks) => Ok(tasks.read(&user)?.accept(&user, &conn)?.upsert(&conn)?),
+        let res = conn.transaction::<_, errors::ServiceError, _>(|| match req {
+            Req::Cmd(cmd) => {
+                let res = match cmd {
+                    // TODO /alias
+                    ReqCmd::Help => ResCmd::Help(cmd_help("root.md")?),
+                    ReqCmd::User(req) => ResCmd::User(req.handle(&user, &conn)?),
+                    ReqCmd::Search(req) => ResCmd::Search(req.handle(&user, &conn)?),
+                    ReqCmd::View(req) => ResCmd::View(req.handle(&user, &conn)?),
+                    ReqCmd::Team(req) => ResCmd::Team(req.handle(&user, &conn)?),
+                    ReqCmd::Tag(req) => ResCmd::Tag(req.handle(&user, &conn)?),
+                    ReqCmd::Log(req) => ResCmd::Log(req.handle(&user, &conn)?),
+                    ReqCmd::Undo(req) => ResCmd::Undo(req.handle(&user, &conn)?),
+                    ReqCmd::Tutorial => ResCmd::Tutorial(cmd_help("tutorial.md")?),
+                    ReqCmd::Coffee => {
+                        return Err(errors::ServiceError::BadRequest("I'm a teapot.".into()))
+                    }
+                };
+                res.audit(&user, &conn)?;
+                Ok(ResBody::Cmd(res))
+            }
+            Req::Tasks(tasks) => Ok(tasks
+                .read(&user, &conn)?
+                .accept(&user, &conn)?
+                .upsert(&user, &conn)?),
+        })?;
+        // noticed once the change is saved, which a failure to send does not undo
+        if let ResBody::Cmd(ResCmd::User(ResUser::Modify(ResModify::Password(_)))) = &res {
+            Email::notice(_email::PasswordChanged {
+                user: users::table.find(user.id).first::<models::User>(&conn)?,
+                tz: user.tz,
+            });
         }
+        Ok(res)
     })
     .await?;
 
@@ -61,6 +93,11 @@ pub enum ReqCmd {
     Help,
     User(ReqUser),
     Search(ReqSearch),
+    View(ReqView),
+    Team(ReqTeam),
+    Tag(ReqTag),
+    Log(ReqLog),
+    Undo(ReqUndo),
     Tutorial,
     Coffee,
 }
@@ -72,20 +109,103 @@ pub enum ReqUser {
     Modify(ReqModify),
 }
 
+#[derive(Debug, PartialEq)]
+pub enum ReqTeam {
+    Help,
+    Info,
+    Create(String),
+    Delete(String),
+    Add(ReqTeamMember),
+    Remove(ReqTeamMember),
+    Permission(ReqTeamPermission),
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ReqTeamMember {
+    pub team: String,
+    pub user: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ReqTeamPermission {
+    pub team: String,
+    pub permission: Option<bool>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReqTag {
+    Help,
+    Info,
+    Rename(ReqTagPair),
+    Merge(ReqTagPair),
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ReqTagPair {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct ReqLog {
+    pub task: Option<i32>,
+    pub actor: Option<String>,
+    pub created_at: Range<models::EasyDateTime>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ReqUndo {
+    pub task: i32,
+    pub version: Option<i32>,
+}
+
 #[derive(Debug, PartialEq)]
 pub enum ReqSearch {
     Help,
-    Condition(Condition),
+    Condition(Query, Page),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReqView {
+    Help,
+    Info,
+    Save(ReqViewSave),
+    Delete(String),
+    Run(ReqViewRun),
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ReqViewSave {
+    pub name: String,
+    pub condition: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ReqViewRun {
+    pub name: String,
+    pub owner: Option<String>,
+    pub page: Page,
 }
 
 #[derive(Debug, PartialEq)]
 pub enum ReqModify {
     Email(String),
+    EmailConfirm(uuid::Uuid),
     Password(PasswordSet),
     Name(String),
-    Timescale(Timescale),
+    Timescale(models::Timescale),
+    Locale(String),
+    Digest(Option<models::EasyTime>),
+    Dst(models::Dst),
+    Tz(String),
+    WeekStart(u32),
+    DateFormat(models::DateFormat),
+    DefaultView(String),
     Allocations(Vec<ReqAllocation>),
     Permission(ReqPermission),
+    RequestPermission(ReqPermissionRequest),
+    Approve(String),
+    Deny(String),
 }
 
 #[derive(Debug, PartialEq)]
@@ -100,17 +220,31 @@ pub type ReqAllocation = models::ResAllocation;
 #[derive(Debug, PartialEq, Serialize)]
 pub struct ReqPermission {
     pub user: String,
+    pub task: Option<i32>,
     pub permission: Option<bool>,
 }
 
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ReqPermissionRequest {
+    pub user: String,
+    pub edit: bool,
+}
+
 #[derive(Serialize)]
 enum ResCmd {
     Help(String),
     User(ResUser),
     Search(ResSearch),
+    View(ResView),
+    Team(ResTeam),
+    Tag(ResTag),
+    Log(Vec<ResAudit>),
+    Undo(ResUndo),
     Tutorial(String),
 }
 
+type ResUndo = ReqUndo;
+
 #[derive(Serialize)]
 enum ResUser {
     Help(String),
@@ -119,6 +253,7 @@ enum ResUser {
         since: DateTime<Utc>,
         executed: i32,
         tz: Tz,
+        settings: models::Settings,
         permissions: ResPermissions,
     },
     Modify(ResModify),
@@ -130,38 +265,128 @@ struct ResPermissions {
     edit_to: Vec<String>,
     view_from: Vec<String>,
     edit_from: Vec<String>,
+    trees_to: Vec<ResTreePermission>,
+    trees_from: Vec<ResTreePermission>,
+    requests_to: Vec<ResPermissionRequest>,
+    requests_from: Vec<ResPermissionRequest>,
+}
+
+#[derive(Serialize)]
+struct ResTreePermission {
+    user: String,
+    task: i32,
+    edit: bool,
 }
 
 #[derive(Serialize)]
 enum ResModify {
     Email(String),
+    EmailPending(String),
     Password(()),
     Name(String),
     Timescale(String),
+    Locale(String),
+    Digest(Option<String>),
+    Dst(String),
+    Tz(Tz),
+    WeekStart(u32),
+    DateFormat(String),
+    DefaultView(String),
     Allocations(Vec<models::ResAllocation>),
     Permission(ResPermission),
+    RequestPermission(ResPermissionRequest),
+    Approve(ResPermission),
+    Deny(String),
 }
 
 type ResPermission = ReqPermission;
+type ResPermissionRequest = ReqPermissionRequest;
+
+#[derive(Queryable, Serialize)]
+struct ResAudit {
+    id: i32,
+    actor: Option<String>,
+    action: String,
+    task: Option<i32>,
+    old: Option<serde_json::Value>,
+    new: Option<serde_json::Value>,
+    created_at: DateTime<Utc>,
+}
 
-#[derive(Debug, PartialEq, Clone)]
-pub enum Timescale {
-    Year,
-    Quarter,
-    Month,
-    Week,
-    Day,
-    Hours,
-    Hour,
-    Minutes,
-    Minute,
-    Second,
+#[derive(Serialize)]
+enum ResTeam {
+    Help(String),
+    Info {
+        teams: Vec<ResTeamInfo>,
+        view_to: Vec<String>,
+        edit_to: Vec<String>,
+    },
+    Create(String),
+    Delete(String),
+    Add(ResTeamMember),
+    Remove(ResTeamMember),
+    Permission(ResTeamPermission),
 }
 
+#[derive(Serialize)]
+struct ResTeamInfo {
+    name: String,
+    owner: String,
+    members: Vec<String>,
+    view_from: Vec<String>,
+    edit_from: Vec<String>,
+}
+
+type ResTeamMember = ReqTeamMember;
+type ResTeamPermission = ReqTeamPermission;
+
+#[derive(Serialize)]
+enum ResTag {
+    Help(String),
+    Info(Vec<ResTagInfo>),
+    Rename(ResTagPair),
+    Merge(ResTagPair),
+}
+
+#[derive(Serialize)]
+struct ResTagInfo {
+    name: String,
+    count: i32,
+}
+
+type ResTagPair = ReqTagPair;
+
 #[derive(Serialize)]
 enum ResSearch {
     Help(String),
-    Condition(Vec<models::ResTask>),
+    Condition(ResPage),
+}
+
+#[derive(Serialize)]
+enum ResView {
+    Help(String),
+    Info(Vec<ResViewInfo>),
+    Save(ResViewSave),
+    Delete(String),
+    Run(ResPage),
+}
+
+#[derive(Serialize)]
+struct ResViewInfo {
+    name: String,
+    owner: String,
+    condition: String,
+}
+
+type ResViewSave = ReqViewSave;
+
+/// Conditions joined by ` ` (and), `|` (or), `!` (not) and parentheses.
+#[derive(Debug, PartialEq)]
+pub enum Query {
+    Term(Condition),
+    Not(Box<Query>),
+    And(Vec<Query>),
+    Or(Vec<Query>),
 }
 
 #[derive(Debug, Default, PartialEq, PartialOrd)]
@@ -176,6 +401,8 @@ pub struct Condition {
     pub title: Option<Expression>,
     pub assign: Option<Expression>,
     pub link: Option<Expression>,
+    pub notes: Option<Expression>,
+    pub tag: Option<String>,
 }
 
 #[derive(Debug, Default, PartialEq, PartialOrd)]
@@ -188,6 +415,10 @@ pub struct Boolean {
 
 type Range<T> = (Option<T>, Option<T>);
 
+type Filter = Box<
+    dyn diesel::BoxableExpression<tasks::table, diesel::pg::Pg, SqlType = diesel::sql_types::Bool>,
+>;
+
 #[derive(Debug, PartialEq, PartialOrd)]
 pub enum Expression {
     Words(Vec<String>),
@@ -201,10 +432,11 @@ pub struct ReqTasks {
 
 #[derive(Debug, Default, PartialEq)]
 pub struct ReqTask {
-    // indent #id joint] * TITLE startable- -deadline $weight @assign [joint link
+    // indent #id joint] * TITLE startable- -deadline $weight @assign +tag [joint [label] link |notes
     pub indent: i32,
     pub attribute: Attribute,
-    pub link: Option<String>,
+    pub links: Vec<models::Link>,
+    pub notes: Option<String>,
 }
 
 #[derive(Debug, Default, PartialEq)]
@@ -217,16 +449,23 @@ pub struct Attribute {
     pub assign: Option<String>,
     pub startable: Option<models::EasyDateTime>,
     pub deadline: Option<models::EasyDateTime>,
+    pub tags: Vec<String>,
     pub title: String,
 }
 
 #[derive(AsChangeset)]
 #[table_name = "users"]
 struct AltUser {
-    email: Option<String>,
     hash: Option<String>,
     name: Option<String>,
     timescale: Option<String>,
+    locale: Option<String>,
+    tz: Option<String>,
+    digest_at: Option<Option<NaiveTime>>,
+    dst: Option<String>,
+    week_start: Option<i32>,
+    date_format: Option<String>,
+    default_view: Option<String>,
 }
 
 impl ReqUser {
@@ -247,7 +486,7 @@ impl ReqUser {
         user: &models::AuthedUser,
         conn: &models::Conn,
     ) -> Result<ResUser, errors::ServiceError> {
-        use crate::schema::tasks::dsl::{assign, is_archived, tasks};
+        use crate::schema::tasks::dsl::{assign, deleted_at, is_archived, tasks};
         use crate::schema::users::dsl::{created_at, email, users};
 
         let (email_, since) = users
@@ -257,6 +496,7 @@ impl ReqUser {
         let executed = tasks
             .filter(assign.eq(&user.id))
             .filter(is_archived)
+            .filter(deleted_at.is_null())
             .count()
             .get_result::<i64>(conn)? as i32;
 
@@ -265,6 +505,7 @@ impl ReqUser {
             since: since,
             executed: executed,
             tz: user.tz,
+            settings: user.settings.clone(),
             permissions: user.permissions(conn)?,
         })
     }
@@ -277,6 +518,10 @@ impl models::AuthedUser {
             edit_to: self.to(true, conn)?,
             view_from: self.from(false, conn)?,
             edit_from: self.from(true, conn)?,
+            trees_to: self.trees_to(conn)?,
+            trees_from: self.trees_from(conn)?,
+            requests_to: self.requests_to(conn)?,
+            requests_from: self.requests_from(conn)?,
         })
     }
     fn to(&self, edit_: bool, conn: &models::Conn) -> Result<Vec<String>, errors::DbError> {
@@ -307,6 +552,703 @@ impl models::AuthedUser {
             ))
             .load::<String>(conn)
     }
+    fn trees_to(&self, conn: &models::Conn) -> Result<Vec<ResTreePermission>, errors::DbError> {
+        use crate::schema::task_permissions::dsl::{edit, task, task_permissions};
+        use crate::schema::tasks::dsl::{assign, id, tasks};
+        use crate::schema::users::dsl::{name, users};
+
+        Ok(task_permissions
+            .inner_join(users)
+            .filter(task.eq_any(tasks.select(id).filter(assign.eq(&self.id))))
+            .select((name, task, edit))
+            .load::<(String, i32, bool)>(conn)?
+            .into_iter()
+            .map(|(user, task_, edit_)| ResTreePermission {
+                user: user,
+                task: task_,
+                edit: edit_,
+            })
+            .collect())
+    }
+    fn trees_from(&self, conn: &models::Conn) -> Result<Vec<ResTreePermission>, errors::DbError> {
+        use crate::schema::task_permissions::dsl::{edit, subject, task, task_permissions};
+        use crate::schema::tasks::dsl::tasks;
+        use crate::schema::users::dsl::{name, users};
+
+        Ok(task_permissions
+            .inner_join(tasks.inner_join(users))
+            .filter(subject.eq(&self.id))
+            .select((name, task, edit))
+            .load::<(String, i32, bool)>(conn)?
+            .into_iter()
+            .map(|(user, task_, edit_)| ResTreePermission {
+                user: user,
+                task: task_,
+                edit: edit_,
+            })
+            .collect())
+    }
+    fn requests_to(
+        &self,
+        conn: &models::Conn,
+    ) -> Result<Vec<ResPermissionRequest>, errors::DbError> {
+        use crate::schema::permission_requests::dsl::*;
+        use crate::schema::users::dsl::{id, name, users};
+
+        Ok(permission_requests
+            .inner_join(users.on(id.eq(object)))
+            .filter(subject.eq(&self.id))
+            .order(created_at)
+            .select((name, edit))
+            .load::<(String, bool)>(conn)?
+            .into_iter()
+            .map(|(user, edit_)| ResPermissionRequest {
+                user: user,
+                edit: edit_,
+            })
+            .collect())
+    }
+    fn requests_from(
+        &self,
+        conn: &models::Conn,
+    ) -> Result<Vec<ResPermissionRequest>, errors::DbError> {
+        use crate::schema::permission_requests::dsl::*;
+        use crate::schema::users::dsl::{id, name, users};
+
+        Ok(permission_requests
+            .inner_join(users.on(id.eq(subject)))
+            .filter(object.eq(&self.id))
+            .order(created_at)
+            .select((name, edit))
+            .load::<(String, bool)>(conn)?
+            .into_iter()
+            .map(|(user, edit_)| ResPermissionRequest {
+                user: user,
+                edit: edit_,
+            })
+            .collect())
+    }
+    /// Grant or revoke a permission over all your items.
+    fn grant(
+        &self,
+        subject_: i32,
+        edit_: Option<bool>,
+        conn: &models::Conn,
+    ) -> Result<(), errors::ServiceError> {
+        use crate::schema::permission_requests::dsl::permission_requests;
+        use crate::schema::permissions::dsl::*;
+
+        diesel::delete(
+            permissions
+                .filter(subject.eq(&subject_))
+                .filter(object.eq(&self.id)),
+        )
+        .execute(conn)?;
+        if let Some(edit_) = edit_ {
+            diesel::insert_into(permissions)
+                .values(&models::Permission {
+                    subject: subject_,
+                    object: self.id,
+                    edit: edit_,
+                })
+                .execute(conn)?;
+            self.notify(subject_, "permission.grant", None, conn)?;
+        }
+        // whatever was asked for has been answered now
+        diesel::delete(permission_requests.find((subject_, self.id))).execute(conn)?;
+        Ok(())
+    }
+    fn request_permission(
+        &self,
+        req: ReqPermissionRequest,
+        conn: &models::Conn,
+    ) -> Result<ResModify, errors::ServiceError> {
+        use crate::schema::permission_requests::dsl::*;
+        use crate::schema::permissions::dsl::permissions;
+        use diesel::dsl::{exists, select};
+
+        let object_ = find_user(&req.user, conn)?;
+        if select(exists(
+            permissions
+                .find((self.id, object_.id))
+                .filter(crate::schema::permissions::edit.ge(&req.edit)),
+        ))
+        .get_result(conn)?
+        {
+            return Err(errors::ServiceError::BadRequest(format!(
+                "{}: already permitted.",
+                req.user
+            )));
+        }
+        diesel::insert_into(permission_requests)
+            .values(&models::PermissionRequest {
+                subject: self.id,
+                object: object_.id,
+                edit: req.edit,
+                created_at: self.clock.now(),
+            })
+            .on_conflict((subject, object))
+            .do_update()
+            .set((edit.eq(&req.edit), created_at.eq(self.clock.now())))
+            .execute(conn)?;
+        if std::env::var("PERMISSION_REQUEST_EMAIL").as_deref() == Ok("true") {
+            let requester = find_user_name(self.id, conn)?;
+            Email::try_from(_email::PermissionRequested {
+                requester: requester,
+                edit: req.edit,
+                object: object_,
+            })?
+            .send()?;
+        }
+        Ok(ResModify::RequestPermission(req))
+    }
+    fn reply_permission(
+        &self,
+        user_: String,
+        approve: bool,
+        conn: &models::Conn,
+    ) -> Result<ResModify, errors::ServiceError> {
+        use crate::schema::permission_requests::dsl::permission_requests;
+
+        let subject_ = find_user(&user_, conn)?;
+        let request = permission_requests
+            .find((subject_.id, self.id))
+            .first::<models::PermissionRequest>(conn)
+            .map_err(|_| {
+                errors::ServiceError::BadRequest(format!("{}: request not found.", user_))
+            })?;
+        if !approve {
+            diesel::delete(&request).execute(conn)?;
+            return Ok(ResModify::Deny(user_));
+        }
+        self.grant(subject_.id, Some(request.edit), conn)?;
+        Ok(ResModify::Approve(ResPermission {
+            user: user_,
+            task: None,
+            permission: Some(request.edit),
+        }))
+    }
+    /// Grant or revoke a permission over one of your trees, rather than over all your items.
+    fn share(
+        &self,
+        subject_: i32,
+        tid: i32,
+        edit_: Option<bool>,
+        conn: &models::Conn,
+    ) -> Result<(), errors::ServiceError> {
+        use crate::schema::task_permissions::dsl::{subject, task, task_permissions};
+        use crate::schema::tasks::dsl::{assign, deleted_at, tasks};
+
+        if tasks
+            .find(tid)
+            .filter(assign.eq(&self.id))
+            .filter(deleted_at.is_null())
+            .first::<models::Task>(conn)
+            .is_err()
+        {
+            return Err(errors::ServiceError::BadRequest(format!(
+                "#{}: item not found, or not your item.",
+                tid,
+            )));
+        }
+        diesel::delete(
+            task_permissions
+                .filter(subject.eq(&subject_))
+                .filter(task.eq(&tid)),
+        )
+        .execute(conn)?;
+        if let Some(edit_) = edit_ {
+            diesel::insert_into(task_permissions)
+                .values(&models::TaskPermission {
+                    subject: subject_,
+                    task: tid,
+                    edit: edit_,
+                })
+                .execute(conn)?;
+            self.notify(subject_, "permission.grant", Some(tid), conn)?;
+        }
+        Ok(())
+    }
+}
+
+impl ReqUndo {
+    fn handle(
+        self,
+        user: &models::AuthedUser,
+        conn: &models::Conn,
+    ) -> Result<ResUndo, errors::ServiceError> {
+        use crate::schema::task_versions::dsl::{id, task, task_versions};
+
+        let permitted = user.permitted(true, conn)?;
+        let old = user.editable(self.task, &permitted, conn)?;
+        let mut query = task_versions.filter(task.eq(&self.task)).into_boxed();
+        if let Some(v) = self.version {
+            query = query.filter(id.eq(v))
+        }
+        let version = query
+            .order(id.desc())
+            .first::<models::TaskVersion>(conn)
+            .map_err(|_| {
+                errors::ServiceError::BadRequest(format!("#{}: version not found.", self.task))
+            })?;
+        if !user.assignable(version.assign, &permitted, conn)? {
+            return Err(errors::ServiceError::BadRequest(format!(
+                "#{}: no permission to assign the version.",
+                self.task
+            )));
+        }
+        // the current row becomes a version too, so the undo itself can be undone
+        old.snapshot(conn)?;
+        let new = diesel::update(&old)
+            .set(&AltTask::from(&version))
+            .get_result::<models::Task>(conn)?;
+        new.set_links(&version.links(), conn)?;
+        new.set_tags(&version.tags(), conn)?;
+        user.audit_task("task.undo", Some(&old), Some(&new), conn)?;
+        Ok(ResUndo {
+            task: self.task,
+            version: Some(version.id),
+        })
+    }
+}
+
+impl ResCmd {
+    /// Record settings and permissions changed by a command.
+    fn audit(
+        &self,
+        user: &models::AuthedUser,
+        conn: &models::Conn,
+    ) -> Result<(), errors::ServiceError> {
+        let action = match self {
+            Self::User(ResUser::Modify(_)) => "user.modify",
+            Self::Team(ResTeam::Help(_)) | Self::Team(ResTeam::Info { .. }) => return Ok(()),
+            Self::Team(_) => "team.modify",
+            Self::View(ResView::Save(_)) | Self::View(ResView::Delete(_)) => "view.modify",
+            Self::Tag(ResTag::Rename(_)) | Self::Tag(ResTag::Merge(_)) => "tag.modify",
+            _ => return Ok(()),
+        };
+        let new = match self {
+            Self::User(ResUser::Modify(res)) => serde_json::to_value(res),
+            Self::Team(res) => serde_json::to_value(res),
+            Self::View(res) => serde_json::to_value(res),
+            Self::Tag(res) => serde_json::to_value(res),
+            _ => unreachable!(),
+        }
+        .map_err(|_| errors::ServiceError::InternalServerError)?;
+        user.audit(action, user.id, None, None, Some(new), conn)
+    }
+}
+
+impl ReqLog {
+    fn handle(
+        self,
+        user: &models::AuthedUser,
+        conn: &models::Conn,
+    ) -> Result<Vec<ResAudit>, errors::ServiceError> {
+        use crate::schema::audits::dsl::*;
+        use crate::schema::permissions::dsl::{object, permissions, subject};
+        use crate::schema::users::dsl::{id as user_id, name, users};
+        use diesel::dsl::exists;
+
+        let permitted = user.permitted(false, conn)?;
+        // your own actions and account, and those on tasks you can view
+        let mut query = audits
+            .left_join(users.on(user_id.nullable().eq(actor)))
+            .filter(
+                actor
+                    .eq(&user.id)
+                    .or(owner.eq(&user.id))
+                    .or(task.is_not_null().and(
+                        owner
+                            .eq_any(&permitted.users)
+                            .or(task.eq_any(&permitted.tasks))
+                            .or(exists(
+                                permissions
+                                    .filter(subject.eq(&user.id))
+                                    .filter(object.nullable().eq(owner)),
+                            )),
+                    )),
+            )
+            .select((
+                id,
+                name.nullable(),
+                action,
+                task,
+                old_values,
+                new_values,
+                created_at,
+            ))
+            .into_boxed();
+        if let Some(tid) = self.task {
+            query = query.filter(task.eq(tid))
+        }
+        if let Some(s) = self.actor {
+            query = query.filter(name.eq(s))
+        }
+        if let Some(dt) = &self.created_at.0 {
+            query = query.filter(created_at.ge(user.globalize(&dt, conn)?))
+        }
+        if let Some(dt) = &self.created_at.1 {
+            query = query.filter(created_at.le(user.globalize(&dt, conn)?))
+        }
+        Ok(query.order(id.desc()).limit(100).load::<ResAudit>(conn)?)
+    }
+}
+
+impl ReqTeam {
+    fn handle(
+        self,
+        user: &models::AuthedUser,
+        conn: &models::Conn,
+    ) -> Result<ResTeam, errors::ServiceError> {
+        use crate::schema::team_members::dsl::{member, team, team_members};
+        use crate::schema::team_permissions::dsl::{object, team as grantee, team_permissions};
+        use crate::schema::teams::dsl::teams;
+        use crate::schema::users::dsl::{id, name, users};
+
+        let res = match self {
+            Self::Help => ResTeam::Help(cmd_help("team.md")?),
+            Self::Info => ResTeam::Info {
+                teams: user.teams(conn)?,
+                view_to: user.teams_to(false, conn)?,
+                edit_to: user.teams_to(true, conn)?,
+            },
+            Self::Create(s) => {
+                let new = diesel::insert_into(teams)
+                    .values(&NewTeam {
+                        name: s.clone(),
+                        owner: user.id,
+                    })
+                    .get_result::<models::Team>(conn)?;
+                diesel::insert_into(team_members)
+                    .values(&models::TeamMember {
+                        team: new.id,
+                        member: user.id,
+                    })
+                    .execute(conn)?;
+                ResTeam::Create(s)
+            }
+            Self::Delete(s) => {
+                diesel::delete(&user.own_team(&s, conn)?).execute(conn)?;
+                ResTeam::Delete(s)
+            }
+            Self::Add(req) => {
+                let team_ = user.own_team(&req.team, conn)?;
+                let member_ = users
+                    .select(id)
+                    .filter(name.eq(&req.user))
+                    .first::<i32>(conn)
+                    .map_err(|_| {
+                        errors::ServiceError::BadRequest(format!("{}: user not found.", req.user))
+                    })?;
+                diesel::insert_into(team_members)
+                    .values(&models::TeamMember {
+                        team: team_.id,
+                        member: member_,
+                    })
+                    .on_conflict_do_nothing()
+                    .execute(conn)?;
+                ResTeam::Add(req)
+            }
+            Self::Remove(req) => {
+                let team_ = find_team(&req.team, conn)?;
+                let member_ = users
+                    .select(id)
+                    .filter(name.eq(&req.user))
+                    .first::<i32>(conn)
+                    .map_err(|_| {
+                        errors::ServiceError::BadRequest(format!("{}: user not found.", req.user))
+                    })?;
+                // the owner removes anyone but themselves, and members may leave
+                if team_.owner == member_ || (team_.owner != user.id && member_ != user.id) {
+                    return Err(errors::ServiceError::BadRequest(format!(
+                        "{}: cannot remove {} from the team.",
+                        req.team, req.user,
+                    )));
+                }
+                diesel::delete(
+                    team_members
+                        .filter(team.eq(&team_.id))
+                        .filter(member.eq(&member_)),
+                )
+                .execute(conn)?;
+                ResTeam::Remove(req)
+            }
+            Self::Permission(req) => {
+                let team_ = find_team(&req.team, conn)?;
+                diesel::delete(
+                    team_permissions
+                        .filter(grantee.eq(&team_.id))
+                        .filter(object.eq(&user.id)),
+                )
+                .execute(conn)?;
+                if let Some(edit_) = req.permission {
+                    diesel::insert_into(team_permissions)
+                        .values(&models::TeamPermission {
+                            team: team_.id,
+                            object: user.id,
+                            edit: edit_,
+                        })
+                        .execute(conn)?;
+                }
+                ResTeam::Permission(req)
+            }
+        };
+        Ok(res)
+    }
+}
+
+impl ReqTag {
+    fn handle(
+        self,
+        user: &models::AuthedUser,
+        conn: &models::Conn,
+    ) -> Result<ResTag, errors::ServiceError> {
+        use crate::schema::task_tags::dsl::{tag, task, task_tags};
+        use crate::schema::tasks::dsl::{deleted_at, tasks};
+
+        let res = match self {
+            Self::Help => ResTag::Help(cmd_help("tag.md")?),
+            Self::Info => {
+                let own = tags::table
+                    .filter(tags::owner.eq(&user.id))
+                    .order(tags::name)
+                    .load::<models::Tag>(conn)?;
+                let mut counts: HashMap<i32, i32> = HashMap::new();
+                for t in task_tags
+                    .inner_join(tasks)
+                    .filter(deleted_at.is_null())
+                    .filter(tag.eq_any(own.iter().map(|t| t.id).collect::<Vec<i32>>()))
+                    .select(tag)
+                    .load::<i32>(conn)?
+                {
+                    *counts.entry(t).or_default() += 1;
+                }
+                ResTag::Info(
+                    own.into_iter()
+                        .map(|t| ResTagInfo {
+                            count: counts.get(&t.id).copied().unwrap_or_default(),
+                            name: t.name,
+                        })
+                        .collect(),
+                )
+            }
+            Self::Rename(req) => {
+                let from = user.own_tag(&req.from, conn)?;
+                if user.own_tag(&req.to, conn).is_ok() {
+                    return Err(errors::ServiceError::BadRequest(format!(
+                        "{}: tag exists, merge into it instead.",
+                        req.to
+                    )));
+                }
+                retag(&from, user, conn, || {
+                    diesel::update(&from)
+                        .set(tags::name.eq(&req.to))
+                        .execute(conn)?;
+                    Ok(())
+                })?;
+                ResTag::Rename(req)
+            }
+            Self::Merge(req) => {
+                let from = user.own_tag(&req.from, conn)?;
+                let to = user.own_tag(&req.to, conn)?;
+                if from.id != to.id {
+                    retag(&from, user, conn, || {
+                        let tagged = task_tags
+                            .filter(tag.eq(&from.id))
+                            .select(task)
+                            .load::<i32>(conn)?;
+                        diesel::insert_into(task_tags)
+                            .values(
+                                tagged
+                                    .into_iter()
+                                    .map(|t| models::TaskTag {
+                                        task: t,
+                                        tag: to.id,
+                                    })
+                                    .collect::<Vec<models::TaskTag>>(),
+                            )
+                            .on_conflict_do_nothing()
+                            .execute(conn)?;
+                        diesel::delete(&from).execute(conn)?;
+                        Ok(())
+                    })?;
+                }
+                ResTag::Merge(req)
+            }
+        };
+        Ok(res)
+    }
+}
+
+/// Rewrites a tag across its live tasks, keeping a version of each and auditing its tags.
+fn retag<F>(
+    from: &models::Tag,
+    user: &models::AuthedUser,
+    conn: &models::Conn,
+    rewrite: F,
+) -> Result<(), errors::ServiceError>
+where
+    F: FnOnce() -> Result<(), errors::ServiceError>,
+{
+    let touched = tasks::table
+        .filter(tasks::deleted_at.is_null())
+        .filter(
+            tasks::id.eq_any(
+                task_tags::table
+                    .filter(task_tags::tag.eq(&from.id))
+                    .select(task_tags::task),
+            ),
+        )
+        .load::<models::Task>(conn)?
+        .into_iter()
+        .map(|t| {
+            t.snapshot(conn)?;
+            let old = t.tags(conn)?;
+            Ok((t, old))
+        })
+        .collect::<Result<Vec<_>, errors::ServiceError>>()?;
+    rewrite()?;
+    for (t, old) in touched {
+        user.audit(
+            "task.tag",
+            t.assign,
+            Some(t.id),
+            Some(serde_json::json!({ "tags": old })),
+            Some(serde_json::json!({ "tags": t.tags(conn)? })),
+            conn,
+        )?;
+    }
+    Ok(())
+}
+
+#[derive(Insertable)]
+#[table_name = "teams"]
+struct NewTeam {
+    name: String,
+    owner: i32,
+}
+
+#[derive(Insertable)]
+#[table_name = "searches"]
+struct NewSearch {
+    owner: i32,
+    name: String,
+    condition: String,
+}
+
+fn find_user(name_: &str, conn: &models::Conn) -> Result<models::User, errors::ServiceError> {
+    use crate::schema::users::dsl::{name, users};
+
+    users
+        .filter(name.eq(name_))
+        .first::<models::User>(conn)
+        .map_err(|_| errors::ServiceError::BadRequest(format!("{}: user not found.", name_)))
+}
+
+fn find_user_name(id_: i32, conn: &models::Conn) -> Result<String, errors::ServiceError> {
+    use crate::schema::users::dsl::{name, users};
+
+    Ok(users.find(id_).select(name).first::<String>(conn)?)
+}
+
+fn find_team(name_: &str, conn: &models::Conn) -> Result<models::Team, errors::ServiceError> {
+    use crate::schema::teams::dsl::{name, teams};
+
+    teams
+        .filter(name.eq(name_))
+        .first::<models::Team>(conn)
+        .map_err(|_| errors::ServiceError::BadRequest(format!("{}: team not found.", name_)))
+}
+
+impl models::AuthedUser {
+    /// Your tag, which is on the items assigned to you.
+    fn own_tag(
+        &self,
+        name_: &str,
+        conn: &models::Conn,
+    ) -> Result<models::Tag, errors::ServiceError> {
+        tags::table
+            .filter(tags::owner.eq(&self.id))
+            .filter(tags::name.eq(name_))
+            .first::<models::Tag>(conn)
+            .map_err(|_| errors::ServiceError::BadRequest(format!("{}: tag not found.", name_)))
+    }
+    fn own_team(
+        &self,
+        name_: &str,
+        conn: &models::Conn,
+    ) -> Result<models::Team, errors::ServiceError> {
+        let team = find_team(name_, conn)?;
+        if team.owner != self.id {
+            return Err(errors::ServiceError::BadRequest(format!(
+                "{}: not your team.",
+                name_
+            )));
+        }
+        Ok(team)
+    }
+    fn teams(&self, conn: &models::Conn) -> Result<Vec<ResTeamInfo>, errors::ServiceError> {
+        use crate::schema::team_members::dsl::{member, team, team_members};
+        use crate::schema::teams::dsl::{id, teams};
+        use crate::schema::users::dsl::{name, users};
+
+        let mut res = Vec::new();
+        for (team_, owner_) in teams
+            .filter(id.eq_any(team_members.filter(member.eq(&self.id)).select(team)))
+            .inner_join(users)
+            .select((crate::schema::teams::all_columns, name))
+            .load::<(models::Team, String)>(conn)?
+        {
+            res.push(ResTeamInfo {
+                members: team_members
+                    .filter(team.eq(&team_.id))
+                    .inner_join(users)
+                    .select(name)
+                    .load::<String>(conn)?,
+                view_from: team_.from(false, conn)?,
+                edit_from: team_.from(true, conn)?,
+                name: team_.name,
+                owner: owner_,
+            })
+        }
+        Ok(res)
+    }
+    fn teams_to(&self, edit_: bool, conn: &models::Conn) -> Result<Vec<String>, errors::DbError> {
+        use crate::schema::team_permissions::dsl::{edit, object, team, team_permissions};
+        use crate::schema::teams::dsl::{id, name, teams};
+
+        teams
+            .select(name)
+            .filter(
+                id.eq_any(
+                    team_permissions
+                        .filter(object.eq(&self.id))
+                        .filter(edit.eq(edit_))
+                        .select(team),
+                ),
+            )
+            .load::<String>(conn)
+    }
+}
+
+impl models::Team {
+    fn from(&self, edit_: bool, conn: &models::Conn) -> Result<Vec<String>, errors::DbError> {
+        use crate::schema::team_permissions::dsl::{edit, object, team, team_permissions};
+        use crate::schema::users::dsl::{id, name, users};
+
+        users
+            .select(name)
+            .filter(
+                id.eq_any(
+                    team_permissions
+                        .filter(team.eq(&self.id))
+                        .filter(edit.eq(edit_))
+                        .select(object),
+                ),
+            )
+            .load::<String>(conn)
+    }
 }
 
 impl ReqModify {
@@ -316,8 +1258,7 @@ impl ReqModify {
         conn: &models::Conn,
     ) -> Result<ResModify, errors::ServiceError> {
         use crate::schema::allocations::dsl::{allocations, owner};
-        use crate::schema::permissions::dsl::*;
-        use crate::schema::users::dsl::{email, id, name, users};
+        use crate::schema::users::dsl::{id, name, users};
         use diesel::dsl::{exists, select};
 
         if let Self::Allocations(req) = self {
@@ -339,40 +1280,40 @@ impl ReqModify {
                 .map_err(|_| {
                     errors::ServiceError::BadRequest(format!("{}: user not found.", req.user))
                 })?;
-            diesel::delete(
-                permissions
-                    .filter(subject.eq(&subject_))
-                    .filter(object.eq(&user.id)),
-            )
-            .execute(conn)?;
-            if let Some(edit_) = req.permission {
-                diesel::insert_into(permissions)
-                    .values(&models::Permission {
-                        subject: subject_,
-                        object: user.id,
-                        edit: edit_,
-                    })
-                    .execute(conn)?;
+            match req.task {
+                Some(tid) => user.share(subject_, tid, req.permission, conn)?,
+                None => user.grant(subject_, req.permission, conn)?,
             }
             return Ok(ResModify::Permission(req));
         }
+        if let Self::RequestPermission(req) = self {
+            return user.request_permission(req, conn);
+        }
+        if let Self::Approve(s) = self {
+            return user.reply_permission(s, true, conn);
+        }
+        if let Self::Deny(s) = self {
+            return user.reply_permission(s, false, conn);
+        }
+        if let Self::Email(s) = self {
+            return user.request_email(s, conn);
+        }
+        if let Self::EmailConfirm(key) = self {
+            return user.confirm_email(key, conn);
+        }
         let mut alt_user = AltUser {
-            email: None,
             hash: None,
             name: None,
             timescale: None,
+            locale: None,
+            tz: None,
+            digest_at: None,
+            dst: None,
+            week_start: None,
+            date_format: None,
+            default_view: None,
         };
         let res = match self {
-            Self::Email(s) => {
-                if select(exists(users.filter(email.eq(&s)))).get_result(conn)? {
-                    return Err(errors::ServiceError::BadRequest(format!(
-                        "email already in use: {}",
-                        s,
-                    )));
-                }
-                alt_user.email = Some(s.clone());
-                ResModify::Email(s)
-            }
             Self::Password(password_set) => {
                 let hash = password_set.verify(user, conn)?;
                 alt_user.hash = Some(hash);
@@ -392,6 +1333,59 @@ impl ReqModify {
                 alt_user.timescale = Some(timescale.as_str().into());
                 ResModify::Timescale(timescale.as_str().into())
             }
+            Self::Locale(s) => {
+                if !_email::TEMPLATES.has(&s) {
+                    return Err(errors::ServiceError::BadRequest(format!(
+                        "{}: locale not supported.",
+                        s,
+                    )));
+                }
+                alt_user.locale = Some(s.clone());
+                ResModify::Locale(s)
+            }
+            Self::Digest(time) => {
+                let at = match time {
+                    None => None,
+                    Some(time) => Some(
+                        NaiveTime::from_hms_opt(
+                            time.h.unwrap_or_default() as u32,
+                            time.m.unwrap_or_default() as u32,
+                            0,
+                        )
+                        .ok_or_else(|| {
+                            errors::ServiceError::BadRequest("time notation invalid.".into())
+                        })?,
+                    ),
+                };
+                alt_user.digest_at = Some(at);
+                ResModify::Digest(at.map(|t| t.format("%H:%M").to_string()))
+            }
+            Self::Dst(policy) => {
+                alt_user.dst = Some(policy.as_str().into());
+                ResModify::Dst(policy.as_str().into())
+            }
+            Self::Tz(s) => {
+                let tz = s.parse::<Tz>().map_err(|_| {
+                    errors::ServiceError::BadRequest(format!("{}: timezone not supported.", s))
+                })?;
+                alt_user.tz = Some(tz.to_string());
+                ResModify::Tz(tz)
+            }
+            Self::WeekStart(w) => {
+                alt_user.week_start = Some(w as i32);
+                ResModify::WeekStart(w)
+            }
+            Self::DateFormat(format) => {
+                alt_user.date_format = Some(format.as_str().into());
+                ResModify::DateFormat(format.as_str().into())
+            }
+            Self::DefaultView(view) => {
+                if !["home", "leaves", "roots", "archives"].contains(&view.as_str()) {
+                    user.find_search(&view, None, conn)?;
+                }
+                alt_user.default_view = Some(view.clone());
+                ResModify::DefaultView(view)
+            }
             _ => unreachable!(),
         };
         diesel::update(user).set(&alt_user).execute(conn)?;
@@ -400,6 +1394,77 @@ impl ReqModify {
     }
 }
 
+impl models::AuthedUser {
+    fn request_email(
+        &self,
+        email_: String,
+        conn: &models::Conn,
+    ) -> Result<ResModify, errors::ServiceError> {
+        use crate::schema::invitations::dsl::{invitations, owner};
+        use crate::schema::users::dsl::{email, locale, users};
+        use diesel::dsl::{exists, select};
+
+        if select(exists(users.filter(email.eq(&email_)))).get_result(conn)? {
+            return Err(errors::ServiceError::BadRequest(format!(
+                "email already in use: {}",
+                email_,
+            )));
+        }
+        // a new request supersedes the pending one
+        diesel::delete(invitations.filter(owner.eq(&self.id))).execute(conn)?;
+        let invitation = diesel::insert_into(invitations)
+            .values(&models::Invitation {
+                id: uuid::Uuid::new_v4(),
+                email: email_.clone(),
+                expires_at: self.clock.now() + chrono::Duration::hours(1),
+                forgot_pw: false,
+                tz: self.tz.to_string(),
+                locale: users.find(self.id).select(locale).first::<String>(conn)?,
+                owner: Some(self.id),
+            })
+            .get_result::<models::Invitation>(conn)?;
+        Email::try_from(invitation)?.send()?;
+
+        Ok(ResModify::EmailPending(email_))
+    }
+    fn confirm_email(
+        &self,
+        key: uuid::Uuid,
+        conn: &models::Conn,
+    ) -> Result<ResModify, errors::ServiceError> {
+        use crate::schema::invitations::dsl::{expires_at, invitations, owner};
+        use crate::schema::users::dsl::{email, users};
+        use diesel::dsl::{exists, select};
+
+        let invitation = invitations
+            .find(&key)
+            .filter(owner.eq(&self.id))
+            .filter(expires_at.gt(&self.clock.now()))
+            .first::<models::Invitation>(conn)
+            .map_err(|_| errors::ServiceError::BadRequest("confirmation key invalid.".into()))?;
+        if select(exists(users.filter(email.eq(&invitation.email)))).get_result(conn)? {
+            return Err(errors::ServiceError::BadRequest(format!(
+                "email already in use: {}",
+                invitation.email,
+            )));
+        }
+        let old_user = users.find(self.id).first::<models::User>(conn)?;
+        let new_user = diesel::update(&old_user)
+            .set(email.eq(&invitation.email))
+            .get_result::<models::User>(conn)?;
+        diesel::delete(&invitation).execute(conn)?;
+        let res = ResModify::Email(new_user.email.clone());
+        Email::try_from(_email::EmailChanged {
+            old_email: old_user.email,
+            user: new_user,
+            tz: self.tz,
+        })?
+        .send()?;
+
+        Ok(res)
+    }
+}
+
 impl PasswordSet {
     fn verify(
         &self,
@@ -431,23 +1496,6 @@ impl PasswordSet {
     }
 }
 
-impl Timescale {
-    pub fn as_str(&self) -> &'static str {
-        match self {
-            Self::Year => "Y",
-            Self::Quarter => "Q",
-            Self::Month => "M",
-            Self::Week => "W",
-            Self::Day => "D",
-            Self::Hours => "6h",
-            Self::Hour => "h",
-            Self::Minutes => "15m",
-            Self::Minute => "m",
-            Self::Second => "s",
-        }
-    }
-}
-
 impl ReqAllocation {
     fn verify(
         &self,
@@ -479,145 +1527,467 @@ impl ReqSearch {
     ) -> Result<ResSearch, errors::ServiceError> {
         let res = match self {
             Self::Help => ResSearch::Help(cmd_help("search.md")?),
-            Self::Condition(con) => ResSearch::Condition(con.extract(user, conn)?),
+            Self::Condition(con, page) => ResSearch::Condition(con.extract(&page, user, conn)?),
         };
         Ok(res)
     }
 }
 
-impl Condition {
+impl ReqView {
+    fn handle(
+        self,
+        user: &models::AuthedUser,
+        conn: &models::Conn,
+    ) -> Result<ResView, errors::ServiceError> {
+        use crate::schema::searches::dsl::*;
+        use diesel::pg::upsert::excluded;
+
+        let res = match self {
+            Self::Help => ResView::Help(cmd_help("view.md")?),
+            Self::Info => ResView::Info(user.searches(conn)?),
+            Self::Save(req) => {
+                diesel::insert_into(searches)
+                    .values(&NewSearch {
+                        owner: user.id,
+                        name: req.name.clone(),
+                        condition: req.condition.clone(),
+                    })
+                    .on_conflict((owner, name))
+                    .do_update()
+                    .set(condition.eq(excluded(condition)))
+                    .execute(conn)?;
+                ResView::Save(req)
+            }
+            Self::Delete(s) => {
+                let count = diesel::delete(searches.filter(owner.eq(&user.id)).filter(name.eq(&s)))
+                    .execute(conn)?;
+                if count == 0 {
+                    return Err(errors::ServiceError::BadRequest(format!(
+                        "{}: search not found.",
+                        s
+                    )));
+                }
+                // a deleted search no longer serves as the default view
+                {
+                    use crate::schema::users::dsl::{default_view, users};
+                    diesel::update(users.find(user.id).filter(default_view.eq(&s)))
+                        .set(default_view.eq("home"))
+                        .execute(conn)?;
+                }
+                ResView::Delete(s)
+            }
+            Self::Run(req) => ResView::Run(
+                user.find_search(&req.name, req.owner.as_deref(), conn)?
+                    .run(&req.page, user, conn)?,
+            ),
+        };
+        Ok(res)
+    }
+}
+
+impl models::AuthedUser {
+    /// Your saved searches and those of users who let you view their items.
+    fn searches(&self, conn: &models::Conn) -> Result<Vec<ResViewInfo>, errors::ServiceError> {
+        use crate::schema::permissions::dsl::{object, permissions, subject};
+        use crate::schema::searches::dsl::*;
+        use crate::schema::users::dsl::{name as user_name, users};
+
+        let permitted = self.permitted(false, conn)?;
+        Ok(searches
+            .filter(
+                owner
+                    .eq(&self.id)
+                    .or(owner.eq_any(permissions.filter(subject.eq(&self.id)).select(object)))
+                    .or(owner.eq_any(&permitted.users)),
+            )
+            .inner_join(users)
+            .select((name, user_name, condition))
+            .order((user_name, name))
+            .load::<(String, String, String)>(conn)?
+            .into_iter()
+            .map(|(n, o, c)| ResViewInfo {
+                name: n,
+                owner: o,
+                condition: c,
+            })
+            .collect())
+    }
+    /// A saved search of yours, or of `owner_` if they let you view their items.
+    pub fn find_search(
+        &self,
+        name_: &str,
+        owner_: Option<&str>,
+        conn: &models::Conn,
+    ) -> Result<models::Search, errors::ServiceError> {
+        use crate::schema::permissions::dsl::{object, permissions, subject};
+        use crate::schema::searches::dsl::*;
+        use diesel::dsl::exists;
+
+        let owner_ = match owner_ {
+            None => self.id,
+            Some(s) => find_user(s, conn)?.id,
+        };
+        let visible = owner_ == self.id
+            || diesel::select(exists(
+                permissions
+                    .filter(subject.eq(&self.id))
+                    .filter(object.eq(&owner_)),
+            ))
+            .get_result(conn)?
+            || self.permitted(false, conn)?.users.contains(&owner_);
+        if visible {
+            if let Ok(search) = searches
+                .filter(owner.eq(&owner_))
+                .filter(name.eq(name_))
+                .first::<models::Search>(conn)
+            {
+                return Ok(search);
+            }
+        }
+        Err(errors::ServiceError::BadRequest(format!(
+            "{}: search not found.",
+            name_
+        )))
+    }
+}
+
+impl models::Search {
+    /// Runs the saved condition, whose sort and limit `page` may override.
+    pub fn run(
+        &self,
+        page: &Page,
+        user: &models::AuthedUser,
+        conn: &models::Conn,
+    ) -> Result<ResPage, errors::ServiceError> {
+        match self.condition.parse::<ReqSearch>()? {
+            ReqSearch::Condition(query, mut saved) => {
+                saved.extend(std::iter::once(page.clone()));
+                query.extract(&saved, user, conn)
+            }
+            ReqSearch::Help => unreachable!(),
+        }
+    }
+}
+
+impl Query {
     fn extract(
         &self,
+        page: &Page,
         user: &models::AuthedUser,
         conn: &models::Conn,
-    ) -> Result<Vec<models::ResTask>, errors::ServiceError> {
+    ) -> Result<ResPage, errors::ServiceError> {
         use crate::schema::arrows::dsl::arrows;
+        use crate::schema::tasks::dsl::*;
+        use crate::schema::users::dsl::users;
 
-        let mut res_tasks = self.query(user, conn)?;
-        self.filter_regex(&mut res_tasks)?;
-        if max(self.context.0, self.context.1).is_some() {
+        let _arrows: models::Arrows = if self.has_context() {
             // TODO /s <#< load all arrows?
-            let _arrows: models::Arrows = arrows.load::<models::Arrow>(conn)?.into();
-            self.filter_context(&mut res_tasks, &_arrows);
+            arrows.load::<models::Arrow>(conn)?.into()
+        } else {
+            models::Arrows { arrows: Vec::new() }
+        };
+        let permitted = user.permitted(false, conn)?;
+        let query = || -> Result<_, errors::ServiceError> {
+            let matched = tasks
+                .filter(self.compile(user, &_arrows, conn)?)
+                .select(id)
+                .into_boxed();
+            Ok(tasks
+                .filter(deleted_at.is_null())
+                .filter(id.eq_any(tasks.filter(permitted.filter()).select(id).into_boxed()))
+                .filter(id.eq_any(matched))
+                .inner_join(users)
+                .into_boxed())
+        };
+        let mut lexemes = Vec::new();
+        self.lexemes(false, &mut lexemes);
+        let lexemes = if lexemes.is_empty() {
+            None
+        } else {
+            // rank by any of the words searched for
+            Some(lexemes.join(" | "))
+        };
+        let total = query()?.count().get_result::<i64>(conn)?;
+        let mut res_tasks = page
+            .apply(
+                query()?.select(models::SelTask::columns()),
+                lexemes,
+                user,
+                conn,
+            )?
+            .load::<models::SelTask>(conn)?
+            .into_iter()
+            .map(|t| t.to_res())
+            .collect::<Vec<models::ResTask>>();
+        models::ResTask::annotate(&mut res_tasks, conn)?;
+        Ok(page.cut(res_tasks, total))
+    }
+    fn compile(
+        &self,
+        user: &models::AuthedUser,
+        _arrows: &models::Arrows,
+        conn: &models::Conn,
+    ) -> Result<Filter, errors::ServiceError> {
+        use diesel::dsl::not;
+        use diesel::sql_types::Bool;
+
+        let filter: Filter = match self {
+            Self::Term(con) => con.compile(user, _arrows, conn)?,
+            // IS NOT TRUE, so that what is unknown to a term, e.g. a null weight, is kept
+            Self::Not(q) => Box::new(not(q
+                .compile(user, _arrows, conn)?
+                .is_not_distinct_from(true))),
+            Self::And(qs) => {
+                let mut filter: Filter = Box::new(true.into_sql::<Bool>());
+                for q in qs {
+                    filter = Box::new(filter.and(q.compile(user, _arrows, conn)?))
+                }
+                filter
+            }
+            Self::Or(qs) => {
+                let mut filter: Filter = Box::new(false.into_sql::<Bool>());
+                for q in qs {
+                    filter = Box::new(filter.or(q.compile(user, _arrows, conn)?))
+                }
+                filter
+            }
+        };
+        Ok(filter)
+    }
+    fn has_context(&self) -> bool {
+        match self {
+            Self::Term(con) => max(con.context.0, con.context.1).is_some(),
+            Self::Not(q) => q.has_context(),
+            Self::And(qs) | Self::Or(qs) => qs.iter().any(|q| q.has_context()),
+        }
+    }
+    /// Lexemes of the words searched for, except the negated ones.
+    fn lexemes(&self, negated: bool, acc: &mut Vec<String>) {
+        match self {
+            Self::Term(_) if negated => (),
+            Self::Term(con) => acc.extend(con.lexemes()),
+            Self::Not(q) => q.lexemes(!negated, acc),
+            Self::And(qs) | Self::Or(qs) => {
+                for q in qs {
+                    q.lexemes(negated, acc)
+                }
+            }
         }
-        Ok(res_tasks)
     }
-    fn query(
+}
+
+/// Patterns run in the database as Postgres regular expressions, so they are checked there too.
+fn valid_regex(regex: &str, conn: &models::Conn) -> Result<(), errors::ServiceError> {
+    use diesel::dsl::sql;
+    use diesel::result::Error;
+    use diesel::sql_types::{Bool, Text};
+
+    // in a savepoint, so that a bad pattern leaves any enclosing transaction usable
+    let checked = conn.transaction::<bool, Error, _>(|| {
+        diesel::select(sql::<Bool>("'' ~* ").bind::<Text, _>(regex)).get_result::<bool>(conn)
+    });
+    match checked {
+        Ok(_) => Ok(()),
+        Err(Error::DatabaseError(_, info)) => Err(errors::ServiceError::BadRequest(format!(
+            "regex error: {}",
+            info.message()
+        ))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+impl Condition {
+    fn compile(
         &self,
         user: &models::AuthedUser,
+        _arrows: &models::Arrows,
         conn: &models::Conn,
-    ) -> Result<Vec<models::ResTask>, errors::ServiceError> {
+    ) -> Result<Filter, errors::ServiceError> {
         use crate::schema::arrows::dsl::*;
-        use crate::schema::permissions::dsl::*;
         use crate::schema::tasks::dsl::*;
-        use crate::schema::users::dsl::{name, users};
-        use diesel::dsl::exists;
-
-        let mut query = tasks
-            .filter(exists(
-                permissions
-                    .filter(subject.eq(&user.id))
-                    .filter(object.eq(assign)),
-            ))
-            .inner_join(users)
-            .select(models::SelTask::columns())
-            .into_boxed();
+        use diesel::dsl::{exists, sql};
+        use diesel::sql_types::{Bool, Text};
 
-        if let Some(b) = &self.boolean.is_archived {
-            query = query.filter(is_archived.eq(b))
+        let mut filter: Filter = Box::new(true.into_sql::<Bool>());
+        if let Some(b) = self.boolean.is_archived {
+            filter = Box::new(filter.and(is_archived.eq(b)))
         }
-        if let Some(b) = &self.boolean.is_starred {
-            query = query.filter(is_starred.eq(b))
+        if let Some(b) = self.boolean.is_starred {
+            filter = Box::new(filter.and(is_starred.eq(b)))
         }
-        if let Some(b) = &self.boolean.is_leaf {
-            query = query.filter(exists(arrows.filter(target.eq(id))).eq(!b))
+        if let Some(b) = self.boolean.is_leaf {
+            filter = Box::new(filter.and(exists(arrows.filter(target.eq(id))).eq(!b)))
         }
-        if let Some(b) = &self.boolean.is_root {
-            query = query.filter(exists(arrows.filter(source.eq(id))).eq(!b))
+        if let Some(b) = self.boolean.is_root {
+            filter = Box::new(filter.and(exists(arrows.filter(source.eq(id))).eq(!b)))
         }
-        if let Some(w) = &self.weight.0 {
-            query = query.filter(weight.ge(w))
+        if let Some(tid) = self.context.0 {
+            let ids = models::Tid::from(tid).nodes_to(models::LR::Root, _arrows);
+            filter = Box::new(filter.and(id.eq_any(ids)))
         }
-        if let Some(w) = &self.weight.1 {
-            query = query.filter(weight.le(w))
+        if let Some(tid) = self.context.1 {
+            let ids = models::Tid::from(tid).nodes_to(models::LR::Leaf, _arrows);
+            filter = Box::new(filter.and(id.eq_any(ids)))
+        }
+        if let Some(w) = self.weight.0 {
+            filter = Box::new(filter.and(weight.ge(w)))
+        }
+        if let Some(w) = self.weight.1 {
+            filter = Box::new(filter.and(weight.le(w)))
         }
         if let Some(dt) = &self.startable.0 {
-            query = query.filter(startable.ge(user.globalize(&dt)?))
+            filter = Box::new(filter.and(startable.ge(user.globalize(&dt, conn)?)))
         }
         if let Some(dt) = &self.startable.1 {
-            query = query.filter(startable.le(user.globalize(&dt)?))
+            filter = Box::new(filter.and(startable.le(user.globalize(&dt, conn)?)))
         }
         if let Some(dt) = &self.deadline.0 {
-            query = query.filter(deadline.ge(user.globalize(&dt)?))
+            filter = Box::new(filter.and(deadline.ge(user.globalize(&dt, conn)?)))
         }
         if let Some(dt) = &self.deadline.1 {
-            query = query.filter(deadline.le(user.globalize(&dt)?))
+            filter = Box::new(filter.and(deadline.le(user.globalize(&dt, conn)?)))
         }
         if let Some(dt) = &self.created_at.0 {
-            query = query.filter(created_at.ge(user.globalize(&dt)?))
+            filter = Box::new(filter.and(created_at.ge(user.globalize(&dt, conn)?)))
         }
         if let Some(dt) = &self.created_at.1 {
-            query = query.filter(created_at.le(user.globalize(&dt)?))
+            filter = Box::new(filter.and(created_at.le(user.globalize(&dt, conn)?)))
         }
         if let Some(dt) = &self.updated_at.0 {
-            query = query.filter(updated_at.ge(user.globalize(&dt)?))
+            filter = Box::new(filter.and(updated_at.ge(user.globalize(&dt, conn)?)))
         }
         if let Some(dt) = &self.updated_at.1 {
-            query = query.filter(updated_at.le(user.globalize(&dt)?))
+            filter = Box::new(filter.and(updated_at.le(user.globalize(&dt, conn)?)))
         }
-        if let Some(Expression::Words(words)) = &self.title {
-            for w in words {
-                query = query.filter(title.like(format!("%{}%", w)))
-            }
+        let lexemes = self.lexemes();
+        if !lexemes.is_empty() {
+            filter = Box::new(
+                filter.and(
+                    sql::<Bool>("tasks.search @@ to_tsquery('simple', ")
+                        .bind::<Text, _>(lexemes.join(" & "))
+                        .sql(")"),
+                ),
+            )
         }
-        if let Some(Expression::Words(words)) = &self.assign {
-            for w in words {
-                query = query.filter(name.like(format!("%{}%", w)))
-            }
+        for (column, regex) in self.literals() {
+            filter =
+                Box::new(filter.and(sql::<Bool>(&format!("{} ~* ", column)).bind::<Text, _>(regex)))
         }
-        if let Some(Expression::Words(words)) = &self.link {
+        if let Some(Expression::Words(words)) = &self.assign {
             for w in words {
-                query = query.filter(link.like(format!("%{}%", w)))
+                filter = Box::new(
+                    filter.and(
+                        assign.eq_any(
+                            users::table
+                                .filter(users::name.like(format!("%{}%", w)))
+                                .select(users::id),
+                        ),
+                    ),
+                )
             }
         }
-        Ok(query
-            .order((is_starred.desc(), updated_at.desc()))
-            .limit(100) // TODO /s limit up to 100?
-            .load::<models::SelTask>(conn)?
-            .into_iter()
-            .map(|t| t.to_res())
-            .collect())
-    }
-    fn filter_regex(&self, tasks: &mut Vec<models::ResTask>) -> Result<(), errors::ServiceError> {
         if let Some(Expression::Regex(regex)) = &self.title {
-            let regex = Regex::new(&regex)?;
-            tasks.retain(|t| regex.is_match(&t.title))
+            valid_regex(regex, conn)?;
+            filter =
+                Box::new(filter.and(sql::<Bool>("tasks.title ~* ").bind::<Text, _>(regex.clone())))
         }
         if let Some(Expression::Regex(regex)) = &self.assign {
-            let regex = Regex::new(&regex)?;
-            tasks.retain(|t| regex.is_match(&t.assign))
+            valid_regex(regex, conn)?;
+            filter = Box::new(
+                filter.and(
+                    assign.eq_any(
+                        users::table
+                            .filter(sql::<Bool>("users.name ~* ").bind::<Text, _>(regex.clone()))
+                            .select(users::id),
+                    ),
+                ),
+            )
         }
         if let Some(Expression::Regex(regex)) = &self.link {
-            let regex = Regex::new(&regex)?;
-            tasks.retain(|t| regex.is_match(&**t.link.as_ref().unwrap_or(&String::new())));
+            valid_regex(regex, conn)?;
+            filter =
+                Box::new(filter.and(
+                    sql::<Bool>("coalesce(tasks.link, '') ~* ").bind::<Text, _>(regex.clone()),
+                ))
         }
-        Ok(())
+        if let Some(Expression::Regex(regex)) = &self.notes {
+            valid_regex(regex, conn)?;
+            filter =
+                Box::new(filter.and(
+                    sql::<Bool>("coalesce(tasks.notes, '') ~* ").bind::<Text, _>(regex.clone()),
+                ))
+        }
+        if let Some(t) = &self.tag {
+            filter = Box::new(
+                filter.and(exists(
+                    task_tags::table
+                        .inner_join(tags::table)
+                        .filter(task_tags::task.eq(id))
+                        .filter(tags::name.eq(t.clone())),
+                )),
+            )
+        }
+        Ok(filter)
     }
-    fn filter_context(&self, tasks: &mut Vec<models::ResTask>, arrows: &models::Arrows) {
-        if let Some(id) = self.context.0 {
-            let ids = models::Tid::from(id).nodes_to(models::LR::Root, arrows);
-            tasks.retain(|t| ids.iter().any(|id| *id == t.id))
+    /// Title, link and notes words as prefix lexemes weighted A, B and C respectively.
+    /// Words of no letters or digits are left to `literals`, as they make no lexemes.
+    pub(super) fn lexemes(&self) -> Vec<String> {
+        let mut lexemes = Vec::new();
+        for (expr, label) in [(&self.title, 'A'), (&self.link, 'B'), (&self.notes, 'C')].iter() {
+            if let Some(Expression::Words(words)) = expr {
+                lexemes.extend(
+                    words
+                        .iter()
+                        .filter(|w| w.chars().any(char::is_alphanumeric))
+                        .map(|w| {
+                            format!(
+                                "'{}':*{}",
+                                w.replace('\\', "\\\\").replace('\'', "''"),
+                                label
+                            )
+                        }),
+                )
+            }
         }
-        if let Some(id) = self.context.1 {
-            let ids = models::Tid::from(id).nodes_to(models::LR::Leaf, arrows);
-            tasks.retain(|t| ids.iter().any(|id| *id == t.id))
+        lexemes
+    }
+    /// Title, link and notes words with symbols, which lexemes drop, as escaped regexes.
+    pub(super) fn literals(&self) -> Vec<(&'static str, String)> {
+        let mut literals = Vec::new();
+        for (expr, column) in [
+            (&self.title, "tasks.title"),
+            (&self.link, "coalesce(tasks.link, '')"),
+            (&self.notes, "coalesce(tasks.notes, '')"),
+        ]
+        .iter()
+        {
+            if let Some(Expression::Words(words)) = expr {
+                literals.extend(
+                    words
+                        .iter()
+                        .filter(|w| !w.chars().all(char::is_alphanumeric))
+                        .map(|w| {
+                            // a backslash before a symbol makes it literal
+                            let regex = w
+                                .chars()
+                                .map(|c| match c {
+                                    c if c.is_alphanumeric() => c.to_string(),
+                                    c => format!("\\{}", c),
+                                })
+                                .collect::<String>();
+                            (*column, regex)
+                        }),
+                )
+            }
         }
+        literals
     }
 }
 
 struct Acceptor {
     tasks: Vec<TmpTask>,
     arrows: TmpArrows,
+    warnings: Vec<String>,
 }
 
 type TmpArrows = models::Arrows;
@@ -631,10 +2001,17 @@ struct TmpTask {
     deadline: Option<DateTime<Utc>>,
     weight: Option<f32>,
     link: Option<String>,
+    notes: Option<String>,
+    links: Vec<models::Link>,
+    tags: Vec<String>,
 }
 
 impl ReqTasks {
-    fn read(self, user: &models::AuthedUser) -> Result<Acceptor, errors::ServiceError> {
+    fn read(
+        self,
+        user: &models::AuthedUser,
+        conn: &models::Conn,
+    ) -> Result<Acceptor, errors::ServiceError> {
         let iter = self.tasks.iter().enumerate().rev();
         let mut tmp_arrows = Vec::new();
         for (src, t) in iter.clone() {
@@ -663,14 +2040,22 @@ impl ReqTasks {
                 });
         }
         let mut tmp_tasks = Vec::new();
+        let mut warnings = Vec::new();
         for t in self.tasks {
+            let mut globalize = |dt: &models::EasyDateTime, field: &str| {
+                let (dt, note) = user.globalize_noted(dt, conn)?;
+                if let Some(note) = note {
+                    warnings.push(format!("{}: {} {}", t.attribute.title, field, note));
+                }
+                Ok::<_, errors::ServiceError>(dt)
+            };
             let mut startable = None;
-            if let Some(dt) = t.attribute.startable {
-                startable = Some(user.globalize(&dt)?)
+            if let Some(dt) = &t.attribute.startable {
+                startable = Some(globalize(dt, "startable")?)
             }
             let mut deadline = None;
-            if let Some(dt) = t.attribute.deadline {
-                deadline = Some(user.globalize(&dt)?)
+            if let Some(dt) = &t.attribute.deadline {
+                deadline = Some(globalize(dt, "deadline")?)
             }
             tmp_tasks.push(TmpTask {
                 id: t.attribute.id,
@@ -680,12 +2065,16 @@ impl ReqTasks {
                 startable: startable,
                 deadline: deadline,
                 weight: t.attribute.weight,
-                link: t.link,
+                link: t.links.first().map(|l| l.url.clone()),
+                notes: t.notes,
+                links: t.links,
+                tags: t.attribute.tags,
             })
         }
         Ok(Acceptor {
             tasks: tmp_tasks,
             arrows: tmp_arrows.into(),
+            warnings: warnings,
         })
     }
 }
@@ -693,6 +2082,7 @@ impl ReqTasks {
 struct Upserter {
     tasks: Vec<TmpTaskOk>,
     arrows: TmpArrows,
+    warnings: Vec<String>,
 }
 
 struct TmpTaskOk {
@@ -704,6 +2094,9 @@ struct TmpTaskOk {
     deadline: Option<DateTime<Utc>>,
     weight: Option<f32>,
     link: Option<String>,
+    notes: Option<String>,
+    links: Vec<models::Link>,
+    tags: Vec<String>,
 }
 
 impl Acceptor {
@@ -714,6 +2107,7 @@ impl Acceptor {
     ) -> Result<Upserter, errors::ServiceError> {
         self.no_loop()?;
         self.valid_sd()?;
+        self.valid_notes()?;
         self.valid_tid_use()?;
         self.valid_tid(user, conn)?;
         let assigns = self.valid_assign(user, conn)?;
@@ -731,12 +2125,16 @@ impl Acceptor {
                 deadline: t.deadline,
                 weight: t.weight,
                 link: t.link,
+                notes: t.notes,
+                links: t.links,
+                tags: t.tags,
             })
             .collect::<Vec<TmpTaskOk>>();
 
         Ok(Upserter {
             tasks: tasks,
             arrows: self.arrows,
+            warnings: self.warnings,
         })
     }
     fn no_loop(&self) -> Result<(), errors::ServiceError> {
@@ -759,6 +2157,21 @@ impl Acceptor {
         }
         Ok(())
     }
+    fn valid_notes(&self) -> Result<(), errors::ServiceError> {
+        if let Some(t) = self.tasks.iter().find(|t| {
+            t.notes
+                .as_ref()
+                .map(|n| NOTES_LIMIT < n.len())
+                .unwrap_or_default()
+        }) {
+            return Err(errors::ServiceError::BadRequest(format!(
+                "{}... notes over {} bytes.",
+                t.title.chars().take(8).collect::<String>(),
+                NOTES_LIMIT,
+            )));
+        }
+        Ok(())
+    }
     fn valid_tid_use(&self) -> Result<(), errors::ServiceError> {
         self.tid_unique()?;
         for path in self.arrows.paths() {
@@ -803,27 +2216,9 @@ impl Acceptor {
         user: &models::AuthedUser,
         conn: &models::Conn,
     ) -> Result<(), errors::ServiceError> {
-        use crate::schema::permissions::dsl::*;
-        use crate::schema::tasks::dsl::{assign, tasks};
-        use diesel::dsl::exists;
-
-        for id in self.ids() {
-            if tasks
-                .find(id)
-                .filter(exists(
-                    permissions
-                        .filter(subject.eq(&user.id))
-                        .filter(object.eq(assign))
-                        .filter(edit),
-                ))
-                .first::<models::Task>(conn)
-                .is_err()
-            {
-                return Err(errors::ServiceError::BadRequest(format!(
-                    "#{}: item not found, or no edit permission.",
-                    id,
-                )));
-            }
+        let permitted = user.permitted(true, conn)?;
+        for tid in self.ids() {
+            user.editable(tid, &permitted, conn)?;
         }
         Ok(())
     }
@@ -836,18 +2231,22 @@ impl Acceptor {
         use crate::schema::users::dsl::{id, name, users};
         use diesel::dsl::exists;
 
+        let permitted = user.permitted(true, conn)?;
         let mut assigns = Vec::new();
         for t in &self.tasks {
             let mut assign = user.id;
             if let Some(name_) = &t.assign {
                 match users
                     .filter(name.eq(&name_))
-                    .filter(exists(
-                        permissions
-                            .filter(subject.eq(&user.id))
-                            .filter(object.eq(id))
-                            .filter(edit),
-                    ))
+                    .filter(
+                        exists(
+                            permissions
+                                .filter(subject.eq(&user.id))
+                                .filter(object.eq(id))
+                                .filter(edit),
+                        )
+                        .or(id.eq_any(&permitted.users)),
+                    )
                     .first::<models::User>(conn)
                 {
                     Ok(someone) => assign = someone.id,
@@ -875,6 +2274,7 @@ struct NewTask {
     deadline: Option<DateTime<Utc>>,
     weight: Option<f32>,
     link: Option<String>,
+    notes: Option<String>,
 }
 
 #[derive(AsChangeset)]
@@ -887,10 +2287,15 @@ struct AltTask {
     deadline: Option<Option<DateTime<Utc>>>,
     weight: Option<Option<f32>>,
     link: Option<Option<String>>,
+    notes: Option<Option<String>>,
 }
 
 impl Upserter {
-    fn upsert(mut self, conn: &models::Conn) -> Result<ResBody, errors::ServiceError> {
+    fn upsert(
+        mut self,
+        user: &models::AuthedUser,
+        conn: &models::Conn,
+    ) -> Result<ResBody, errors::ServiceError> {
         use crate::schema::arrows::dsl::arrows;
         use crate::schema::tasks::dsl::tasks;
 
@@ -898,24 +2303,35 @@ impl Upserter {
         let mut created = 0;
         let mut updated = 0;
         for t in self.tasks.into_iter() {
-            let id = match t.id {
+            let links = t.links.clone();
+            let tags = t.tags.clone();
+            let new = match t.id {
                 None => {
-                    let id = diesel::insert_into(tasks)
+                    let new = diesel::insert_into(tasks)
                         .values(&NewTask::from(t))
-                        .get_result::<models::Task>(conn)?
-                        .id;
+                        .get_result::<models::Task>(conn)?;
+                    user.audit_task("task.create", None, Some(&new), conn)?;
+                    user.notify(new.assign, "task.assign", Some(new.id), conn)?;
                     created += 1;
-                    id
+                    new
                 }
                 Some(id) => {
-                    diesel::update(tasks.find(id))
+                    let old = tasks.find(id).first::<models::Task>(conn)?;
+                    old.snapshot(conn)?;
+                    let new = diesel::update(&old)
                         .set(&AltTask::from(t))
-                        .execute(conn)?;
+                        .get_result::<models::Task>(conn)?;
+                    user.audit_task("task.update", Some(&old), Some(&new), conn)?;
+                    if old.assign != new.assign {
+                        user.notify(new.assign, "task.assign", Some(new.id), conn)?;
+                    }
                     updated += 1;
-                    id
+                    new
                 }
             };
-            permanents.push(id)
+            new.set_links(&links, conn)?;
+            new.set_tags(&tags, conn)?;
+            permanents.push(new.id)
         }
         for arw in &mut self.arrows.arrows {
             arw.source = *permanents.get(arw.source as usize).unwrap();
@@ -928,6 +2344,7 @@ impl Upserter {
         Ok(ResBody::Tasks {
             created: created,
             updated: updated,
+            warnings: self.warnings,
         })
     }
 }
@@ -942,6 +2359,22 @@ impl From<TmpTaskOk> for NewTask {
             deadline: tmp.deadline,
             weight: tmp.weight,
             link: tmp.link,
+            notes: tmp.notes,
+        }
+    }
+}
+
+impl From<&models::TaskVersion> for AltTask {
+    fn from(version: &models::TaskVersion) -> Self {
+        Self {
+            title: Some(version.title.clone()),
+            assign: Some(version.assign),
+            is_starred: Some(version.is_starred),
+            startable: Some(version.startable),
+            deadline: Some(version.deadline),
+            weight: Some(version.weight),
+            link: Some(version.link.clone()),
+            notes: Some(version.notes.clone()),
         }
     }
 }
@@ -956,6 +2389,7 @@ impl From<TmpTaskOk> for AltTask {
             deadline: Some(tmp.deadline),
             weight: Some(tmp.weight),
             link: Some(tmp.link),
+            notes: Some(tmp.notes),
         }
     }
 }
@@ -964,3 +2398,325 @@ fn cmd_help(filename: &str) -> std::io::Result<String> {
     let path = std::path::Path::new(&utils::env_var("CMD_HELP_DIR")).join(filename);
     std::fs::read_to_string(path)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing;
+
+    #[test]
+    fn t_log_hides_others_accounts() {
+        use crate::schema::permissions::dsl::permissions;
+        use crate::schema::tasks::dsl::{assign, id, tasks, title};
+
+        let pool = match testing::pool() {
+            Some(pool) => pool,
+            None => return,
+        };
+        let conn = pool.get().unwrap();
+        let viewer = testing::user("log_viewer", &conn);
+        let owner = testing::user("log_owner", &conn);
+        diesel::insert_into(permissions)
+            .values(&models::Permission {
+                subject: viewer.id,
+                object: owner.id,
+                edit: false,
+            })
+            .execute(&conn)
+            .unwrap();
+        let tid = diesel::insert_into(tasks)
+            .values((title.eq("logged"), assign.eq(owner.id)))
+            .returning(id)
+            .get_result::<i32>(&conn)
+            .unwrap();
+        owner
+            .audit("task.create", owner.id, Some(tid), None, None, &conn)
+            .unwrap();
+        owner
+            .audit("user.modify", owner.id, None, None, None, &conn)
+            .unwrap();
+        viewer
+            .audit("user.modify", viewer.id, None, None, None, &conn)
+            .unwrap();
+        let log = |user: &models::AuthedUser| {
+            ReqLog {
+                task: None,
+                actor: None,
+                created_at: (None, None),
+            }
+            .handle(user, &conn)
+            .unwrap()
+            .into_iter()
+            .map(|a| (a.actor.unwrap(), a.action))
+            .collect::<Vec<(String, String)>>()
+        };
+        let pair = |actor: &str, action: &str| (String::from(actor), String::from(action));
+        assert_eq!(
+            log(&viewer),
+            vec![
+                pair("log_viewer", "user.modify"),
+                pair("log_owner", "task.create"),
+            ]
+        );
+        assert_eq!(
+            log(&owner),
+            vec![
+                pair("log_owner", "user.modify"),
+                pair("log_owner", "task.create"),
+            ]
+        );
+    }
+    #[test]
+    fn t_valid_regex() {
+        let pool = match testing::pool() {
+            Some(pool) => pool,
+            None => return,
+        };
+        let conn = pool.get().unwrap();
+        // lookahead is fine in Postgres, named groups are not
+        assert!(valid_regex(r"a(?=b)", &conn).is_ok());
+        let bad = |regex: &str| match valid_regex(regex, &conn) {
+            Err(errors::ServiceError::BadRequest(_)) => true,
+            _ => false,
+        };
+        assert!(bad(r"(?P<name>a)"));
+        assert!(bad(r"(a"));
+        // and the connection is still usable
+        assert!(valid_regex(r"^draft", &conn).is_ok());
+    }
+    #[test]
+    fn t_reload_other_sessions() {
+        let pool = match testing::pool() {
+            Some(pool) => pool,
+            None => return,
+        };
+        let conn = pool.get().unwrap();
+        let user = testing::user("session_keeper", &conn);
+        // another session, as kept in its identity before settings were
+        let identity = format!(r#"{{"id":{},"tz":"UTC"}}"#, user.id);
+        let other = serde_json::from_str::<models::AuthedUser>(&identity).unwrap();
+        assert_eq!(other.settings, models::Settings::default());
+        ReqModify::Tz("Asia/Tokyo".into())
+            .exec(&user, &conn)
+            .unwrap();
+        ReqModify::WeekStart(6).exec(&user, &conn).unwrap();
+        let reloaded = other.reloaded(&conn).unwrap();
+        assert_eq!(reloaded.tz, Tz::Asia__Tokyo);
+        assert_eq!(reloaded.settings.week_start, 6);
+    }
+    #[test]
+    fn t_history_and_undo() {
+        use crate::schema::permissions::dsl::permissions;
+        use chrono::TimeZone;
+
+        let pool = match testing::pool() {
+            Some(pool) => pool,
+            None => return,
+        };
+        let conn = pool.get().unwrap();
+        std::env::set_var("INDENT", "    ");
+        let owner = testing::user("undo_owner", &conn);
+        let other = testing::user("undo_other", &conn);
+        let viewer = testing::user("undo_viewer", &conn);
+        for (subject, object, edit) in vec![
+            (owner.id, other.id, true),
+            (viewer.id, owner.id, false),
+            (viewer.id, other.id, false),
+        ] {
+            diesel::insert_into(permissions)
+                .values(&models::Permission {
+                    subject: subject,
+                    object: object,
+                    edit: edit,
+                })
+                .execute(&conn)
+                .unwrap();
+        }
+        let deadline_ = Utc.ymd(2021, 6, 1).and_hms(0, 0, 0);
+        let tid = diesel::insert_into(tasks::table)
+            .values((
+                tasks::title.eq("first"),
+                tasks::assign.eq(owner.id),
+                tasks::deadline.eq(Some(deadline_)),
+            ))
+            .returning(tasks::id)
+            .get_result::<i32>(&conn)
+            .unwrap();
+        let edit = |text: String| match (ReqBody { text: text }).wash().parse::<Req>().unwrap() {
+            Req::Tasks(req) => {
+                req.read(&owner, &conn)
+                    .unwrap()
+                    .accept(&owner, &conn)
+                    .unwrap()
+                    .upsert(&owner, &conn)
+                    .unwrap();
+            }
+            _ => unreachable!(),
+        };
+        edit(format!("#{} second -2021/07/01 @undo_other", tid));
+        edit(format!("#{} third -2021/08/01 @undo_other", tid));
+        let versions =
+            serde_json::to_value(super::super::history::versions(tid, &viewer, &conn).unwrap())
+                .unwrap()["versions"]
+                .as_array()
+                .unwrap()
+                .clone();
+        let titles = versions
+            .iter()
+            .map(|v| v["title"].as_str().unwrap())
+            .collect::<Vec<&str>>();
+        assert_eq!(titles, vec!["second", "first"]);
+        assert_eq!(versions[0]["assign"], "undo_other");
+        assert_eq!(versions[1]["assign"], "undo_owner");
+        let first = versions[1]["id"].as_i64().unwrap() as i32;
+
+        let undo = |user: &models::AuthedUser| {
+            ReqUndo {
+                task: tid,
+                version: Some(first),
+            }
+            .handle(user, &conn)
+        };
+        assert!(undo(&viewer).is_err());
+        undo(&owner).unwrap();
+        let task = tasks::table.find(tid).first::<models::Task>(&conn).unwrap();
+        assert_eq!(task.title, "first");
+        assert_eq!(task.deadline, Some(deadline_));
+        assert_eq!(task.assign, owner.id);
+        // the state undone is kept as the latest version
+        let versions =
+            serde_json::to_value(super::super::history::versions(tid, &owner, &conn).unwrap())
+                .unwrap();
+        assert_eq!(versions["versions"][0]["title"], "third");
+        assert_eq!(versions["versions"].as_array().unwrap().len(), 3);
+    }
+    #[test]
+    fn t_merge_tags_keeps_versions() {
+        use crate::schema::audits::dsl::{action, audits, new_values, old_values, task};
+
+        let pool = match testing::pool() {
+            Some(pool) => pool,
+            None => return,
+        };
+        let conn = pool.get().unwrap();
+        let owner = testing::user("merge_owner", &conn);
+        let tid = diesel::insert_into(tasks::table)
+            .values((tasks::title.eq("tagged"), tasks::assign.eq(owner.id)))
+            .returning(tasks::id)
+            .get_result::<i32>(&conn)
+            .unwrap();
+        let tagged = tasks::table.find(tid).first::<models::Task>(&conn).unwrap();
+        tagged
+            .set_tags(&["old".to_string(), "new".to_string()], &conn)
+            .unwrap();
+        ReqTag::Merge(ReqTagPair {
+            from: "old".into(),
+            to: "new".into(),
+        })
+        .handle(&owner, &conn)
+        .unwrap();
+        assert_eq!(tagged.tags(&conn).unwrap(), vec!["new"]);
+        let versions =
+            serde_json::to_value(super::super::history::versions(tid, &owner, &conn).unwrap())
+                .unwrap();
+        assert_eq!(
+            versions["versions"][0]["tags"],
+            serde_json::json!(["new", "old"])
+        );
+        let audited = audits
+            .filter(action.eq("task.tag"))
+            .filter(task.eq(tid))
+            .select((old_values, new_values))
+            .load::<(Option<serde_json::Value>, Option<serde_json::Value>)>(&conn)
+            .unwrap();
+        assert_eq!(
+            audited,
+            vec![(
+                Some(serde_json::json!({ "tags": ["new", "old"] })),
+                Some(serde_json::json!({ "tags": ["new"] }))
+            )]
+        );
+    }
+    #[test]
+    fn t_not_keeps_nulls() {
+        use chrono::TimeZone;
+
+        let pool = match testing::pool() {
+            Some(pool) => pool,
+            None => return,
+        };
+        let conn = pool.get().unwrap();
+        let user = testing::user("negator", &conn);
+        let insert = |weight: Option<f32>, deadline: Option<DateTime<Utc>>| {
+            diesel::insert_into(tasks::table)
+                .values((
+                    tasks::title.eq("negated"),
+                    tasks::assign.eq(user.id),
+                    tasks::weight.eq(weight),
+                    tasks::deadline.eq(deadline),
+                ))
+                .returning(tasks::id)
+                .get_result::<i32>(&conn)
+                .unwrap()
+        };
+        let light = insert(Some(3.), Some(Utc.ymd(2021, 6, 1).and_hms(0, 0, 0)));
+        let heavy = insert(Some(7.), Some(Utc.ymd(2021, 9, 1).and_hms(0, 0, 0)));
+        let unknown = insert(None, None);
+        let arrows = models::Arrows { arrows: Vec::new() };
+        let search = |condition: &str| {
+            let query = match condition.parse::<ReqSearch>().unwrap() {
+                ReqSearch::Condition(query, _) => query,
+                _ => unreachable!(),
+            };
+            tasks::table
+                .filter(query.compile(&user, &arrows, &conn).unwrap())
+                .filter(tasks::id.eq_any(vec![light, heavy, unknown]))
+                .select(tasks::id)
+                .order(tasks::id)
+                .load::<i32>(&conn)
+                .unwrap()
+        };
+        assert_eq!(search("$<5"), vec![light]);
+        assert_eq!(search("!$<5"), vec![heavy, unknown]);
+        assert_eq!(search("d<2021/7/1"), vec![light]);
+        assert_eq!(search("!d<2021/7/1"), vec![heavy, unknown]);
+        assert_eq!(search("!(d<2021/7/1 $<5)"), vec![heavy, unknown]);
+    }
+    #[test]
+    fn t_words_with_symbols() {
+        let pool = match testing::pool() {
+            Some(pool) => pool,
+            None => return,
+        };
+        let conn = pool.get().unwrap();
+        let user = testing::user("symbolist", &conn);
+        let insert = |title_: &str| {
+            diesel::insert_into(tasks::table)
+                .values((tasks::title.eq(title_), tasks::assign.eq(user.id)))
+                .returning(tasks::id)
+                .get_result::<i32>(&conn)
+                .unwrap()
+        };
+        let cpp = insert("c++ tips");
+        let c = insert("c tips");
+        let wow = insert("wow!!! tips");
+        let arrows = models::Arrows { arrows: Vec::new() };
+        let search = |condition: &str| {
+            let query = match condition.parse::<ReqSearch>().unwrap() {
+                ReqSearch::Condition(query, _) => query,
+                _ => unreachable!(),
+            };
+            tasks::table
+                .filter(query.compile(&user, &arrows, &conn).unwrap())
+                .filter(tasks::id.eq_any(vec![cpp, c, wow]))
+                .select(tasks::id)
+                .order(tasks::id)
+                .load::<i32>(&conn)
+                .unwrap()
+        };
+        assert_eq!(search(r#""c""#), vec![cpp, c]);
+        assert_eq!(search(r#""c++""#), vec![cpp]);
+        assert_eq!(search(r#""!!!""#), vec![wow]);
+        assert_eq!(search(r#""tips" "!!!""#), vec![wow]);
+    }
+}
@@ -0,0 +1,238 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::errors;
+use crate::models::{self, Selectable};
+use crate::schema::trashed_arrows;
+
+#[derive(Serialize)]
+pub struct ResBody {
+    tasks: Vec<ResTrashed>,
+}
+
+#[derive(Serialize)]
+struct ResTrashed {
+    #[serde(flatten)]
+    task: models::ResTask,
+    deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct ReqRestore {
+    tasks: Vec<i32>,
+}
+
+#[derive(Serialize)]
+pub struct ResRestore {
+    count: usize,
+}
+
+pub async fn trash(
+    user: models::AuthedUser,
+    pool: web::Data<models::Pool>,
+) -> Result<HttpResponse, errors::ServiceError> {
+    let res_body = web::block(move || {
+        let conn = pool.get().unwrap();
+        Ok(ResBody {
+            tasks: trashed(&user, &conn)?,
+        })
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(res_body))
+}
+
+pub async fn restore(
+    req: web::Json<ReqRestore>,
+    user: models::AuthedUser,
+    pool: web::Data<models::Pool>,
+) -> Result<HttpResponse, errors::ServiceError> {
+    let res_body = web::block(move || {
+        let conn = pool.get().unwrap();
+        conn.transaction(|| {
+            Ok(ResRestore {
+                count: restore_tasks(&user, &req.tasks, &conn)?,
+            })
+        })
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(res_body))
+}
+
+/// Tasks in the trash you may edit, and so restore.
+fn trashed(
+    user: &models::AuthedUser,
+    conn: &models::Conn,
+) -> Result<Vec<ResTrashed>, errors::ServiceError> {
+    use crate::schema::tasks::dsl::{deleted_at, id, tasks};
+    use crate::schema::users::dsl::users;
+
+    let editable = tasks
+        .filter(user.permitted(true, conn)?.filter())
+        .select(id)
+        .into_boxed();
+    Ok(tasks
+        .filter(id.eq_any(editable))
+        .filter(deleted_at.is_not_null())
+        .inner_join(users)
+        .select((models::SelTask::columns(), deleted_at))
+        .order(deleted_at.desc())
+        .load::<(models::SelTask, Option<DateTime<Utc>>)>(conn)?
+        .into_iter()
+        .map(|(t, dt)| ResTrashed {
+            task: t.to_res(),
+            deleted_at: dt,
+        })
+        .collect())
+}
+
+#[derive(Insertable)]
+#[table_name = "trashed_arrows"]
+struct TrashedArrow {
+    source: i32,
+    target: i32,
+}
+
+/// Move tasks into the trash, taking their arrows along.
+pub fn discard(
+    user: &models::AuthedUser,
+    ids: &Vec<i32>,
+    conn: &models::Conn,
+) -> Result<(), errors::ServiceError> {
+    use crate::schema::arrows::dsl::{arrows, source, target};
+    use crate::schema::tasks::dsl::{deleted_at, id, tasks};
+
+    let now = user.clock.now();
+    for old in tasks
+        .filter(id.eq_any(ids))
+        .filter(deleted_at.is_null())
+        .load::<models::Task>(conn)?
+    {
+        let new = diesel::update(&old)
+            .set(deleted_at.eq(now))
+            .get_result::<models::Task>(conn)?;
+        user.audit_task("task.trash", Some(&old), Some(&new), conn)?;
+    }
+    let removed = diesel::delete(arrows.filter(source.eq_any(ids).or(target.eq_any(ids))))
+        .get_results::<models::Arrow>(conn)?
+        .into_iter()
+        .map(|arw| TrashedArrow {
+            source: arw.source,
+            target: arw.target,
+        })
+        .collect::<Vec<TrashedArrow>>();
+    diesel::insert_into(trashed_arrows::table)
+        .values(&removed)
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Take tasks you may edit out of the trash and wire back arrows between live tasks.
+fn restore_tasks(
+    user: &models::AuthedUser,
+    ids: &Vec<i32>,
+    conn: &models::Conn,
+) -> Result<usize, errors::ServiceError> {
+    use crate::schema::arrows::dsl::arrows;
+    use crate::schema::tasks::dsl::{deleted_at, id, tasks};
+    use crate::schema::trashed_arrows::dsl::{source, target};
+
+    let mut restored = Vec::new();
+    for old in tasks
+        .filter(id.eq_any(ids))
+        .filter(user.permitted(true, conn)?.filter())
+        .filter(deleted_at.is_not_null())
+        .load::<models::Task>(conn)?
+    {
+        let new = diesel::update(&old)
+            .set(deleted_at.eq(None::<DateTime<Utc>>))
+            .get_result::<models::Task>(conn)?;
+        user.audit_task("task.restore", Some(&old), Some(&new), conn)?;
+        restored.push(new.id);
+    }
+    let candidates = trashed_arrows::table
+        .filter(source.eq_any(&restored).or(target.eq_any(&restored)))
+        .load::<models::Arrow>(conn)?;
+    let ends = candidates
+        .iter()
+        .flat_map(|arw| vec![arw.source, arw.target])
+        .collect::<Vec<i32>>();
+    let alive = tasks
+        .filter(id.eq_any(&ends))
+        .filter(deleted_at.is_null())
+        .select(id)
+        .load::<i32>(conn)?;
+    let back = candidates
+        .into_iter()
+        .filter(|arw| alive.contains(&arw.source) && alive.contains(&arw.target))
+        .collect::<Vec<models::Arrow>>();
+    for arw in &back {
+        diesel::delete(trashed_arrows::table.find((arw.source, arw.target))).execute(conn)?;
+    }
+    diesel::insert_into(arrows)
+        .values(&back)
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+    let _arrows: models::Arrows = arrows.load::<models::Arrow>(conn)?.into();
+    if _arrows.has_cycle() {
+        return Err(errors::ServiceError::BadRequest(
+            "restoring would make a loop.".into(),
+        ));
+    }
+    Ok(restored.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing;
+
+    #[test]
+    fn t_editor_restores() {
+        use crate::schema::permissions::dsl::permissions;
+        use crate::schema::tasks::dsl::{assign, id, tasks, title};
+
+        let pool = match testing::pool() {
+            Some(pool) => pool,
+            None => return,
+        };
+        let conn = pool.get().unwrap();
+        let owner = testing::user("trash_owner", &conn);
+        let editor = testing::user("trash_editor", &conn);
+        let viewer = testing::user("trash_viewer", &conn);
+        for (subject, edit) in vec![(editor.id, true), (viewer.id, false)] {
+            diesel::insert_into(permissions)
+                .values(&models::Permission {
+                    subject: subject,
+                    object: owner.id,
+                    edit: edit,
+                })
+                .execute(&conn)
+                .unwrap();
+        }
+        let tid = diesel::insert_into(tasks)
+            .values((title.eq("trashed"), assign.eq(owner.id)))
+            .returning(id)
+            .get_result::<i32>(&conn)
+            .unwrap();
+        discard(&editor, &vec![tid], &conn).unwrap();
+        let ids = |user: &models::AuthedUser| {
+            trashed(user, &conn)
+                .unwrap()
+                .into_iter()
+                .map(|t| t.task.id)
+                .filter(|i| *i == tid)
+                .collect::<Vec<i32>>()
+        };
+        assert_eq!(ids(&owner), vec![tid]);
+        assert_eq!(ids(&editor), vec![tid]);
+        assert!(ids(&viewer).is_empty());
+        assert_eq!(restore_tasks(&viewer, &vec![tid], &conn).unwrap(), 0);
+        assert_eq!(restore_tasks(&editor, &vec![tid], &conn).unwrap(), 1);
+        assert!(ids(&owner).is_empty());
+    }
+}
@@ -21,6 +21,8 @@ struct ResBody {
     tz: Tz,
     timescale: String,
     allocations: Vec<models::ResAllocation>,
+    settings: models::Settings,
+    unread: i64,
 }
 
 pub async fn login(
@@ -66,16 +68,25 @@ pub async fn logout(id: Identity) -> HttpResponse {
 
 impl ReqBody {
     fn to_authed(&self, conn: &models::Conn) -> Result<models::AuthedUser, errors::ServiceError> {
-        use crate::schema::users::dsl::{email, users};
+        use crate::schema::users::dsl::{email, tz, users};
 
         if let Ok(user) = users
             .filter(email.eq(&self.email))
             .first::<models::User>(conn)
         {
             if utils::verify(&user.hash, &self.password)? {
+                // the timezone of the first login is saved until modified by `/u -z`
+                let saved = user.tz.as_ref().and_then(|s| s.parse::<Tz>().ok());
+                if saved.is_none() {
+                    diesel::update(&user)
+                        .set(tz.eq(self.tz.to_string()))
+                        .execute(conn)?;
+                }
                 return Ok(models::AuthedUser {
                     id: user.id,
-                    tz: self.tz,
+                    tz: saved.unwrap_or(self.tz),
+                    clock: models::Clock::default(),
+                    settings: models::Settings::from(&user),
                 });
             }
         }
@@ -103,6 +114,8 @@ impl models::AuthedUser {
             tz: self.tz,
             timescale: user.timescale,
             allocations: _allocations,
+            settings: self.settings.clone(),
+            unread: super::app::notification::unread(self, conn)?,
         })
     }
 }
@@ -1,9 +1,11 @@
 use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
 use chrono_tz::Tz;
 use diesel::prelude::*;
 use serde::Deserialize;
+use std::convert::TryFrom;
 
-use super::_email::Email;
+use super::_email::{self, Email};
 use crate::errors;
 use crate::models;
 
@@ -12,17 +14,19 @@ pub struct ReqBody {
     email: String,
     forgot_pw: bool,
     tz: Tz,
+    locale: Option<String>,
 }
 
 pub async fn invite(
     req: web::Json<ReqBody>,
     pool: web::Data<models::Pool>,
+    clock: web::Data<models::Clock>,
 ) -> Result<HttpResponse, errors::ServiceError> {
     let _ = web::block(move || {
         let conn = pool.get().unwrap();
-        let invitation: models::Invitation = req.into_inner().accept(&conn)?;
+        let invitation: models::Invitation = req.into_inner().accept(clock.now(), &conn)?;
         dbg!(&invitation);
-        Email::from(invitation).send()
+        Email::try_from(invitation)?.send()
     })
     .await?;
 
@@ -30,9 +34,21 @@ pub async fn invite(
 }
 
 impl ReqBody {
-    fn accept(self, conn: &models::Conn) -> Result<models::Invitation, errors::ServiceError> {
+    fn accept(
+        self,
+        now: DateTime<Utc>,
+        conn: &models::Conn,
+    ) -> Result<models::Invitation, errors::ServiceError> {
         use crate::schema::invitations::dsl::invitations;
 
+        if let Some(locale) = &self.locale {
+            if !_email::TEMPLATES.has(locale) {
+                return Err(errors::ServiceError::BadRequest(format!(
+                    "{}: locale not supported.",
+                    locale
+                )));
+            }
+        }
         let user_exists = user_exists(&self.email, conn)?;
         if user_exists && !self.forgot_pw {
             return Err(errors::ServiceError::BadRequest(
@@ -44,22 +60,23 @@ impl ReqBody {
                 "user does not exist yet.".into(),
             ));
         }
-        let invitation: models::Invitation = self.into();
+        let invitation = self.to_invitation(now);
 
         Ok(diesel::insert_into(invitations)
             .values(&invitation)
             .get_result(conn)?)
     }
-}
-
-impl From<ReqBody> for models::Invitation {
-    fn from(req: ReqBody) -> Self {
-        Self {
+    fn to_invitation(self, now: DateTime<Utc>) -> models::Invitation {
+        models::Invitation {
             id: uuid::Uuid::new_v4(),
-            email: req.email,
-            expires_at: chrono::Utc::now() + chrono::Duration::hours(1),
-            forgot_pw: req.forgot_pw,
-            tz: req.tz.to_string(),
+            email: self.email,
+            expires_at: now + chrono::Duration::hours(1),
+            forgot_pw: self.forgot_pw,
+            tz: self.tz.to_string(),
+            locale: self
+                .locale
+                .unwrap_or_else(|| _email::DEFAULT_LOCALE.to_string()),
+            owner: None,
         }
     }
 }
@@ -1,10 +1,12 @@
 use actix_web::{web, HttpResponse};
-use chrono::NaiveTime;
+use chrono::{DateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
 use diesel::prelude::*;
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 use serde::{Deserialize, Serialize};
 
+use super::_email::{self, Email};
 use crate::errors;
 use crate::models;
 use crate::schema::users;
@@ -21,14 +23,19 @@ pub struct ReqBody {
 pub async fn register(
     req: web::Json<ReqBody>,
     pool: web::Data<models::Pool>,
+    clock: web::Data<models::Clock>,
 ) -> Result<HttpResponse, errors::ServiceError> {
     let _ = web::block(move || {
         let conn = pool.get().unwrap();
         let req = req.into_inner();
+        let now = clock.now();
         if req.reset_pw {
-            req.to_alt(&conn)?.update(&req, &conn)?;
+            let (alt_user, tz) = req.to_alt(now, &conn)?;
+            let user = alt_user.update(&req, &conn)?;
+            // the password is changed already
+            Email::notice(_email::PasswordChanged { user: user, tz: tz });
         } else {
-            req.to_new(&conn)?.insert(&conn)?;
+            req.to_new(now, &conn)?.insert(&conn)?;
         };
         Ok(())
     })
@@ -38,32 +45,47 @@ pub async fn register(
 }
 
 impl ReqBody {
-    fn to_new(&self, conn: &models::Conn) -> Result<NewUser, errors::ServiceError> {
-        self.accept(conn)?;
+    fn to_new(
+        &self,
+        now: DateTime<Utc>,
+        conn: &models::Conn,
+    ) -> Result<NewUser, errors::ServiceError> {
+        let invitation = self.accept(now, conn)?;
         Ok(NewUser {
             email: self.email.to_owned(),
             hash: utils::hash(&self.password)?,
             name: self.email.to_owned(),
+            locale: invitation.locale,
         })
     }
-    fn to_alt(&self, conn: &models::Conn) -> Result<AltUser, errors::ServiceError> {
-        self.accept(conn)?;
-        Ok(AltUser {
-            hash: Some(utils::hash(&self.password)?),
-        })
+    fn to_alt(
+        &self,
+        now: DateTime<Utc>,
+        conn: &models::Conn,
+    ) -> Result<(AltUser, Tz), errors::ServiceError> {
+        let invitation = self.accept(now, conn)?;
+        Ok((
+            AltUser {
+                hash: Some(utils::hash(&self.password)?),
+            },
+            invitation.tz()?,
+        ))
     }
-    fn accept(&self, conn: &models::Conn) -> Result<(), errors::ServiceError> {
-        use crate::schema::invitations::dsl::{email, expires_at, invitations};
+    fn accept(
+        &self,
+        now: DateTime<Utc>,
+        conn: &models::Conn,
+    ) -> Result<models::Invitation, errors::ServiceError> {
+        use crate::schema::invitations::dsl::{email, expires_at, invitations, owner};
 
-        diesel::delete(
-            invitations.filter(expires_at.lt(&chrono::Utc::now()))
-        ).execute(conn)?;
         if let Ok(invitation) = invitations
             .find(&self.key)
             .filter(email.eq(&self.email))
+            .filter(expires_at.gt(&now))
+            .filter(owner.is_null())
             .first::<models::Invitation>(conn) {
                 diesel::delete(&invitation).execute(conn)?;
-                return Ok(());
+                return Ok(invitation);
             }
         Err(errors::ServiceError::BadRequest(
             "invitation invalid.".into()
@@ -77,6 +99,7 @@ struct NewUser {
     email: String,
     hash: String,
     name: String,
+    locale: String,
 }
 
 impl NewUser {
@@ -116,14 +139,17 @@ struct AltUser {
 }
 
 impl AltUser {
-    fn update(&self, req: &ReqBody, conn: &models::Conn) -> Result<(), errors::ServiceError> {
+    fn update(
+        &self,
+        req: &ReqBody,
+        conn: &models::Conn,
+    ) -> Result<models::User, errors::ServiceError> {
         use crate::schema::users::dsl::{email, users};
 
         let old_user = users
             .filter(email.eq(&req.email))
             .first::<models::User>(conn)?;
-        diesel::update(&old_user).set(self).execute(conn)?;
-        Ok(())
+        Ok(diesel::update(&old_user).set(self).get_result(conn)?)
     }
 }
 
@@ -153,6 +179,7 @@ pub async fn get_account(
             email: email.clone(),
             hash: utils::hash(&email)?,
             name: email,
+            locale: _email::DEFAULT_LOCALE.into(),
         };
         user.insert(&conn)?;
         Ok(ResBody::from(user))
@@ -0,0 +1,65 @@
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::sync::Mutex;
+
+use crate::errors;
+use crate::models;
+
+pub mod digest;
+pub mod housekeeping;
+
+static STATUS: Lazy<Mutex<Vec<Status>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Outcome of the last run of a job.
+#[derive(Clone, Serialize)]
+pub struct Status {
+    pub name: &'static str,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub count: Option<usize>,
+    pub error: Option<String>,
+}
+
+/// Run `job` on the blocking pool every `period` seconds, recording how each run went.
+pub fn spawn<F>(name: &'static str, period: u64, pool: models::Pool, clock: models::Clock, job: F)
+where
+    F: Fn(&models::Pool, DateTime<Utc>) -> Result<usize, errors::ServiceError>
+        + Clone
+        + Send
+        + 'static,
+{
+    actix_rt::spawn(async move {
+        let mut interval = actix_rt::time::interval(std::time::Duration::from_secs(period));
+        loop {
+            interval.tick().await;
+            let pool = pool.clone();
+            let job = job.clone();
+            let started_at = clock.now();
+            let result = web::block(move || job(&pool, started_at))
+                .await
+                .map_err(errors::ServiceError::from);
+            if let Err(err) = &result {
+                println!("{} Error:\n{:#?}", name, err);
+            }
+            record(Status {
+                name: name,
+                started_at: started_at,
+                finished_at: clock.now(),
+                count: result.as_ref().ok().copied(),
+                error: result.err().map(|err| err.to_string()),
+            });
+        }
+    });
+}
+
+fn record(status: Status) {
+    let mut statuses = STATUS.lock().unwrap();
+    statuses.retain(|s| s.name != status.name);
+    statuses.push(status);
+}
+
+pub fn status() -> Vec<Status> {
+    STATUS.lock().unwrap().clone()
+}
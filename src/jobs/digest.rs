@@ -0,0 +1,253 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use chrono_tz::Tz;
+use diesel::prelude::*;
+use std::convert::TryFrom;
+
+use crate::errors;
+use crate::handlers::_email::{self, Email};
+use crate::handlers::app::home;
+use crate::models;
+
+const PERIOD: u64 = 60; // seconds
+
+pub fn spawn(pool: models::Pool, clock: models::Clock) {
+    super::spawn("Digest", PERIOD, pool, clock, run);
+}
+
+/// Send today's digest to every user whose digest time has come, returning how many were sent.
+fn run(pool: &models::Pool, now: DateTime<Utc>) -> Result<usize, errors::ServiceError> {
+    use crate::schema::users::dsl::{digest_at, users};
+
+    let conn = pool.get().unwrap();
+    let mut count = 0;
+    for user in users
+        .filter(digest_at.is_not_null())
+        .load::<models::User>(&conn)?
+    {
+        match send_if_due(user, now, &conn) {
+            Ok(true) => count += 1,
+            Ok(false) => (),
+            Err(err) => println!("Digest Error:\n{:#?}", err),
+        }
+    }
+    Ok(count)
+}
+
+fn send_if_due(
+    user: models::User,
+    now: DateTime<Utc>,
+    conn: &models::Conn,
+) -> Result<bool, errors::ServiceError> {
+    use crate::schema::users::dsl::{digest_sent_at, users};
+
+    let tz = user
+        .tz
+        .as_ref()
+        .and_then(|tz| tz.parse::<Tz>().ok())
+        .unwrap_or(Tz::UTC);
+    if !is_due(&user, &tz, now) {
+        return Ok(false);
+    }
+    // claim today's digest first so that neither restarts nor other instances send it twice
+    let claimed = diesel::update(
+        users
+            .find(user.id)
+            .filter(digest_sent_at.is_not_distinct_from(user.digest_sent_at)),
+    )
+    .set(digest_sent_at.eq(now))
+    .execute(conn)?;
+    if claimed == 0 {
+        return Ok(false);
+    }
+    let (uid, sent_at) = (user.id, user.digest_sent_at);
+    let sent = compose_and_send(user, tz, now, conn);
+    if sent.is_err() {
+        // release the claim so that the next run retries
+        diesel::update(users.find(uid).filter(digest_sent_at.eq(now)))
+            .set(digest_sent_at.eq(sent_at))
+            .execute(conn)?;
+    }
+    sent
+}
+
+fn compose_and_send(
+    user: models::User,
+    tz: Tz,
+    now: DateTime<Utc>,
+    conn: &models::Conn,
+) -> Result<bool, errors::ServiceError> {
+    let since = user.digest_sent_at.unwrap_or(now - Duration::days(1));
+    let tasks = home::Config::Home.query(
+        &models::AuthedUser {
+            id: user.id,
+            tz: tz,
+            clock: models::Clock::Fixed(now),
+            settings: models::Settings::from(&user),
+        },
+        conn,
+    )?;
+    let digest = compose(user, tz, now, since, tasks);
+    if digest.due_today.is_empty() && digest.overdue.is_empty() && digest.at_risk.is_empty() {
+        return Ok(false);
+    }
+    Email::try_from(digest)?.send()?;
+    Ok(true)
+}
+
+fn is_due(user: &models::User, tz: &Tz, now: DateTime<Utc>) -> bool {
+    let local = now.with_timezone(tz);
+    match (user.digest_at, user.digest_sent_at) {
+        (None, _) => false,
+        (Some(at), _) if local.time() < at => false,
+        (Some(_), None) => true,
+        (Some(_), Some(sent)) => sent.with_timezone(tz).date() < local.date(),
+    }
+}
+
+fn compose(
+    user: models::User,
+    tz: Tz,
+    now: DateTime<Utc>,
+    since: DateTime<Utc>,
+    tasks: Vec<models::ResTask>,
+) -> _email::Digest {
+    let date = now.with_timezone(&tz).date().naive_local();
+    let tomorrow = tz
+        .from_local_datetime(&date.succ().and_hms(0, 0, 0))
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or(now + Duration::days(1));
+    let mut digest = _email::Digest {
+        user: user,
+        tz: tz,
+        date: date,
+        due_today: Vec::new(),
+        overdue: Vec::new(),
+        at_risk: Vec::new(),
+    };
+    for t in tasks {
+        // positive priority: the chain cannot meet its deadline on the current schedule
+        let is_late = t.priority.map(|p| 0. < p).unwrap_or_default();
+        match t.deadline {
+            Some(dl) if dl <= now => {
+                if since < dl {
+                    digest.overdue.push(t)
+                }
+            }
+            Some(dl) if dl < tomorrow => digest.due_today.push(t),
+            _ if is_late => digest.at_risk.push(t),
+            _ => (),
+        }
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveTime;
+
+    fn user(digest_sent_at: Option<DateTime<Utc>>) -> models::User {
+        let since = Utc.ymd(2021, 1, 1).and_hms(0, 0, 0);
+        models::User {
+            id: 1,
+            email: String::from("satun@example.com"),
+            hash: String::new(),
+            name: String::from("satun"),
+            timescale: String::from("6h"),
+            created_at: since,
+            updated_at: since,
+            locale: String::from("en"),
+            tz: Some(String::from("Asia/Tokyo")),
+            digest_at: Some(NaiveTime::from_hms(8, 0, 0)),
+            digest_sent_at: digest_sent_at,
+            dst: String::from("earlier"),
+            week_start: 0,
+            date_format: String::from("ymd"),
+            default_view: String::from("home"),
+        }
+    }
+    fn task(id: i32, deadline: Option<DateTime<Utc>>, priority: Option<f32>) -> models::ResTask {
+        models::ResTask {
+            id: id,
+            title: format!("task{}", id),
+            deadline: deadline,
+            priority: priority,
+            ..Default::default()
+        }
+    }
+    #[test]
+    fn t_is_due() {
+        let tz = Tz::Asia__Tokyo;
+        // 2021/06/10 07:59 and 08:00 in Tokyo
+        let before = Utc.ymd(2021, 6, 9).and_hms(22, 59, 0);
+        let after = Utc.ymd(2021, 6, 9).and_hms(23, 0, 0);
+        // sent on 2021/06/09 08:00 and 2021/06/10 08:00 in Tokyo
+        let yesterday = Utc.ymd(2021, 6, 8).and_hms(23, 0, 0);
+        let today = after;
+        assert!(!is_due(&user(None), &tz, before));
+        assert!(is_due(&user(None), &tz, after));
+        assert!(is_due(&user(Some(yesterday)), &tz, after));
+        assert!(!is_due(
+            &user(Some(today)),
+            &tz,
+            after + Duration::hours(12)
+        ));
+    }
+    #[test]
+    fn t_compose_and_send_to_sink() {
+        let sink = std::env::temp_dir().join(format!("sprig-digest-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&sink).unwrap();
+        let templates = _email::Templates::load(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/src/handlers/_email_templates"),
+            String::from("Sprig"),
+            String::from("sprig@example.com"),
+        );
+
+        let tz = Tz::Asia__Tokyo;
+        let now = Utc.ymd(2021, 6, 9).and_hms(23, 0, 0); // 2021/06/10 08:00 in Tokyo
+        let since = now - Duration::days(1);
+        let tasks = vec![
+            task(1, Some(now + Duration::hours(3)), None),
+            task(2, Some(now - Duration::hours(3)), None),
+            task(3, Some(now - Duration::days(3)), Some(1.)),
+            task(4, Some(now + Duration::days(3)), Some(2.)),
+            task(5, Some(now + Duration::days(3)), Some(-2.)),
+            task(6, None, Some(1.)),
+        ];
+        let digest = compose(user(None), tz, now, since, tasks);
+        let ids = |ts: &Vec<models::ResTask>| ts.iter().map(|t| t.id).collect::<Vec<i32>>();
+        assert_eq!(ids(&digest.due_today), vec![1]);
+        assert_eq!(ids(&digest.overdue), vec![2]);
+        assert_eq!(ids(&digest.at_risk), vec![4, 6]);
+
+        digest.email(&templates).unwrap().sink(&sink).unwrap();
+        let sent = std::fs::read_dir(&sink)
+            .unwrap()
+            .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect::<Vec<String>>();
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0].contains("To: satun@example.com"));
+        assert!(sent[0].contains("Subject: Sprig Deadlines 2021/06/10 Thu"));
+        assert!(sent[0].contains("- #1 task1 (2021/06/10 Thu 11:00)"));
+        assert!(sent[0].contains("- #2 task2 (2021/06/10 Thu 05:00)"));
+        assert!(!sent[0].contains("task3"));
+
+        // rendered in the date format and to the day
+        let mut user = user(None);
+        user.timescale = String::from("D");
+        user.date_format = String::from("dmy");
+        let tasks = vec![task(1, Some(now + Duration::hours(3)), None)];
+        let digest = compose(user, tz, now, since, tasks);
+        std::fs::remove_dir_all(&sink).unwrap();
+        std::fs::create_dir_all(&sink).unwrap();
+        digest.email(&templates).unwrap().sink(&sink).unwrap();
+        let sent = std::fs::read_dir(&sink)
+            .unwrap()
+            .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect::<Vec<String>>();
+        assert!(sent[0].contains("Subject: Sprig Deadlines 10/06/2021 Thu"));
+        assert!(sent[0].contains("- #1 task1 (10/06/2021 Thu)"));
+        std::fs::remove_dir_all(&sink).unwrap();
+    }
+}
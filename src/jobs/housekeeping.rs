@@ -0,0 +1,175 @@
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use std::path::PathBuf;
+
+use crate::errors;
+use crate::handlers::app::attachment;
+use crate::models;
+use crate::utils;
+
+/// What is kept and where, read from the environment once.
+#[derive(Clone)]
+pub struct Config {
+    /// days archived tasks are kept, or 0 to keep them forever
+    archive_retention: i64,
+    /// days deleted tasks are kept in the trash, or 0 to keep them forever
+    trash_retention: i64,
+    attachment_dir: PathBuf,
+}
+
+pub fn spawn(pool: models::Pool, clock: models::Clock) {
+    let period = utils::env_var("HOUSEKEEPING_INTERVAL")
+        .parse::<u64>()
+        .expect("HOUSEKEEPING_INTERVAL must be seconds");
+    let config = Config {
+        archive_retention: utils::env_var("ARCHIVE_RETENTION_DAYS")
+            .parse::<i64>()
+            .expect("ARCHIVE_RETENTION_DAYS must be days"),
+        trash_retention: utils::env_var("TRASH_RETENTION_DAYS")
+            .parse::<i64>()
+            .expect("TRASH_RETENTION_DAYS must be days"),
+        attachment_dir: attachment::DIR.clone(),
+    };
+    super::spawn("Housekeeping", period, pool, clock, move |pool, now| {
+        run(pool, now, &config)
+    });
+}
+
+/// Delete rows and files nobody can use any longer, returning how many were deleted.
+///
+/// Sessions live in signed cookies alone, so they expire on the client side.
+fn run(
+    pool: &models::Pool,
+    now: DateTime<Utc>,
+    config: &Config,
+) -> Result<usize, errors::ServiceError> {
+    use crate::schema::invitations::dsl::{expires_at as invitation_expires_at, invitations};
+    use crate::schema::tasks::dsl::{deleted_at, is_archived, tasks, updated_at};
+    use crate::schema::tokens::dsl::{expires_at as token_expires_at, tokens};
+
+    let conn = pool.get().unwrap();
+    let mut count = 0;
+    count += diesel::delete(invitations.filter(invitation_expires_at.lt(&now))).execute(&conn)?;
+    count += diesel::delete(tokens.filter(token_expires_at.lt(&now))).execute(&conn)?;
+    if 0 < config.archive_retention {
+        count += diesel::delete(
+            tasks
+                .filter(is_archived)
+                .filter(updated_at.lt(now - Duration::days(config.archive_retention))),
+        )
+        .execute(&conn)?;
+    }
+    if 0 < config.trash_retention {
+        count += diesel::delete(
+            tasks.filter(deleted_at.lt(now - Duration::days(config.trash_retention))),
+        )
+        .execute(&conn)?;
+    }
+    count += attachment::sweep(&config.attachment_dir, now, &conn)?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing;
+
+    #[test]
+    fn t_run() {
+        use crate::schema::invitations::dsl::invitations;
+        use crate::schema::tasks::dsl::{
+            assign, deleted_at, id, is_archived, tasks, title, updated_at,
+        };
+        use crate::schema::tokens::dsl::tokens;
+
+        let pool = match testing::pool() {
+            Some(pool) => pool,
+            None => return,
+        };
+        let now = Utc::now();
+        let dir = std::env::temp_dir().join(format!("sprig-housekeeping-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("orphan"), b"orphan").unwrap();
+        let config = Config {
+            archive_retention: 30,
+            trash_retention: 30,
+            attachment_dir: dir.clone(),
+        };
+        let conn = pool.get().unwrap();
+        let user = testing::user("housekeeper", &conn);
+        let task = |archived: bool, updated: DateTime<Utc>, deleted: Option<DateTime<Utc>>| {
+            diesel::insert_into(tasks)
+                .values((
+                    title.eq("housekeeping"),
+                    assign.eq(user.id),
+                    is_archived.eq(archived),
+                    updated_at.eq(updated),
+                    deleted_at.eq(deleted),
+                ))
+                .returning(id)
+                .get_result::<i32>(&conn)
+                .unwrap()
+        };
+        let old_archive = task(true, now - Duration::days(40), None);
+        let new_archive = task(true, now - Duration::days(10), None);
+        let old_trash = task(false, now, Some(now - Duration::days(40)));
+        let new_trash = task(false, now, Some(now - Duration::days(10)));
+        let invitation = diesel::insert_into(invitations)
+            .values(&models::Invitation {
+                id: uuid::Uuid::new_v4(),
+                email: String::from("housekeeper@example.com"),
+                expires_at: now - Duration::minutes(1),
+                forgot_pw: false,
+                tz: String::from("UTC"),
+                locale: String::from("en"),
+                owner: None,
+            })
+            .get_result::<models::Invitation>(&conn)
+            .unwrap();
+        let token = diesel::insert_into(tokens)
+            .values(&models::Token {
+                id: uuid::Uuid::new_v4(),
+                owner: user.id,
+                expires_at: now - Duration::minutes(1),
+                tasks: Vec::new(),
+            })
+            .get_result::<models::Token>(&conn)
+            .unwrap();
+        drop(conn);
+
+        // orphan files are kept for an hour
+        assert!(5 <= run(&pool, now + Duration::hours(2), &config).unwrap());
+
+        let conn = pool.get().unwrap();
+        let mut left = tasks
+            .filter(id.eq_any(vec![old_archive, new_archive, old_trash, new_trash]))
+            .select(id)
+            .load::<i32>(&conn)
+            .unwrap();
+        left.sort();
+        assert_eq!(left, vec![new_archive, new_trash]);
+        assert!(invitations
+            .find(invitation.id)
+            .first::<models::Invitation>(&conn)
+            .is_err());
+        assert!(tokens.find(token.id).first::<models::Token>(&conn).is_err());
+        assert!(!dir.join("orphan").exists());
+        drop(conn);
+
+        // 0 days keeps them forever
+        let forever = Config {
+            archive_retention: 0,
+            trash_retention: 0,
+            attachment_dir: dir.clone(),
+        };
+        run(&pool, now + Duration::days(365), &forever).unwrap();
+        let conn = pool.get().unwrap();
+        let left = tasks
+            .filter(id.eq_any(vec![new_archive, new_trash]))
+            .count()
+            .get_result::<i64>(&conn)
+            .unwrap();
+        assert_eq!(left, 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
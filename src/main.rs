@@ -6,9 +6,11 @@ use actix_identity::{CookieIdentityPolicy, IdentityService};
 use actix_web::{cookie::SameSite, middleware, web, App, HttpServer};
 use diesel::prelude::*;
 use diesel::r2d2::{self, ConnectionManager};
+use once_cell::sync::Lazy;
 
 mod errors;
 mod handlers;
+mod jobs;
 mod models;
 mod schema;
 mod utils;
@@ -29,11 +31,16 @@ async fn main() -> std::io::Result<()> {
             "DATABASE_URL",
         )))
         .expect("Failed to create pool.");
+    let clock = models::Clock::System;
+    Lazy::force(&handlers::_email::TEMPLATES);
+    jobs::digest::spawn(pool.clone(), clock);
+    jobs::housekeeping::spawn(pool.clone(), clock);
 
     HttpServer::new(move || {
         let is_cross_origin = utils::env_var("IS_CROSS_ORIGIN").parse::<bool>().unwrap();
         App::new()
             .data(pool.clone())
+            .data(clock)
             .wrap(middleware::Logger::default())
             .wrap(if !is_cross_origin {
                 Cors::default()
@@ -87,6 +94,7 @@ async fn main() -> std::io::Result<()> {
 fn auth_protected(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::resource("/tasks")
+            .app_data(web::JsonConfig::default().limit(handlers::app::text::BODY_LIMIT))
             .route(web::get().to(handlers::app::home::home))
             .route(web::post().to(handlers::app::text::text))
             .route(web::put().to(handlers::app::exec::exec))
@@ -96,5 +104,39 @@ fn auth_protected(cfg: &mut web::ServiceConfig) {
         web::resource("/task/{tid}")
             .route(web::get().to(handlers::app::focus::focus))
             .route(web::put().to(handlers::app::star::star)),
-    );
+    )
+    .service(
+        web::resource("/task/{tid}/attachments")
+            .app_data(web::PayloadConfig::new(*handlers::app::attachment::LIMIT))
+            .route(web::post().to(handlers::app::attachment::upload)),
+    )
+    .service(
+        web::resource("/task/{tid}/attachments/{aid}")
+            .route(web::get().to(handlers::app::attachment::download)),
+    )
+    .service(
+        web::resource("/task/{tid}/comments")
+            .route(web::get().to(handlers::app::comment::list))
+            .route(web::post().to(handlers::app::comment::add)),
+    )
+    .service(
+        web::resource("/task/{tid}/comments/{cid}")
+            .route(web::put().to(handlers::app::comment::edit))
+            .route(web::delete().to(handlers::app::comment::delete)),
+    )
+    .service(
+        web::resource("/task/{tid}/history")
+            .route(web::get().to(handlers::app::history::history)),
+    )
+    .service(
+        web::resource("/notifications")
+            .route(web::get().to(handlers::app::notification::notifications))
+            .route(web::put().to(handlers::app::notification::read)),
+    )
+    .service(
+        web::resource("/trash")
+            .route(web::get().to(handlers::app::trash::trash))
+            .route(web::put().to(handlers::app::trash::restore)),
+    )
+    .service(web::resource("/jobs").route(web::get().to(handlers::app::jobs::jobs)));
 }
@@ -1,10 +1,14 @@
-use actix_web::{dev::Payload, Error, FromRequest, HttpRequest};
-use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use actix_web::{dev::Payload, web, Error, FromRequest, HttpRequest};
+use chrono::{
+    DateTime, Datelike, Duration, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, Offset,
+    TimeZone, Timelike, Utc,
+};
 use chrono_tz::Tz;
 use diesel::prelude::*;
 use diesel::{r2d2::ConnectionManager, PgConnection};
-use futures::future::{err, ok, Ready};
+use futures::future::LocalBoxFuture;
 use serde::{Deserialize, Serialize};
+use std::cmp::{max, min};
 use std::collections::HashMap;
 use std::ops::Not;
 
@@ -13,6 +17,9 @@ use crate::schema::*;
 
 pub type Pool = r2d2::Pool<ConnectionManager<PgConnection>>;
 pub type Conn = r2d2::PooledConnection<ConnectionManager<PgConnection>>;
+pub type TaskFilter = Box<
+    dyn diesel::BoxableExpression<tasks::table, diesel::pg::Pg, SqlType = diesel::sql_types::Bool>,
+>;
 
 // FROM SCHEMA
 
@@ -29,6 +36,28 @@ pub struct Arrow {
     pub target: i32,
 }
 
+#[derive(Queryable, Identifiable)]
+pub struct Attachment {
+    pub id: i32,
+    pub task: i32,
+    pub name: String,
+    pub content_type: String,
+    pub size: i32,
+    pub digest: String, // SHA-256 of the content, naming its file
+    pub uploaded_by: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Queryable, Identifiable)]
+pub struct Comment {
+    pub id: i32,
+    pub task: i32,
+    pub author: i32,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub edited_at: Option<DateTime<Utc>>,
+}
+
 #[derive(Queryable, Identifiable, Insertable, Debug)]
 pub struct Invitation {
     pub id: uuid::Uuid,
@@ -36,6 +65,8 @@ pub struct Invitation {
     pub expires_at: DateTime<Utc>,
     pub forgot_pw: bool,
     pub tz: String,
+    pub locale: String,
+    pub owner: Option<i32>,
 }
 
 #[derive(Queryable, Identifiable, Insertable)]
@@ -46,7 +77,64 @@ pub struct Permission {
     pub edit: bool,
 }
 
+#[derive(Queryable, Identifiable, Insertable)]
+#[primary_key(subject, object)]
+pub struct PermissionRequest {
+    pub subject: i32,
+    pub object: i32,
+    pub edit: bool,
+    pub created_at: DateTime<Utc>,
+}
+
 #[derive(Queryable, Identifiable)]
+#[table_name = "searches"]
+pub struct Search {
+    pub id: i32,
+    pub owner: i32,
+    pub name: String,
+    pub condition: String,
+}
+
+#[derive(Queryable, Identifiable)]
+pub struct Tag {
+    pub id: i32,
+    pub owner: i32, // the assignee of the tasks tagged
+    pub name: String,
+}
+
+#[derive(Queryable, Identifiable, Insertable)]
+#[primary_key(subject, task)]
+pub struct TaskPermission {
+    pub subject: i32,
+    pub task: i32,
+    pub edit: bool,
+}
+
+#[derive(Queryable, Insertable)]
+pub struct TaskTag {
+    pub task: i32,
+    pub tag: i32,
+}
+
+#[derive(Queryable, Identifiable)]
+pub struct TaskVersion {
+    pub id: i32,
+    pub task: i32,
+    pub title: String,
+    pub assign: i32,
+    pub is_starred: bool,
+    pub startable: Option<DateTime<Utc>>,
+    pub deadline: Option<DateTime<Utc>>,
+    pub weight: Option<f32>,
+    pub link: Option<String>,
+    pub notes: Option<String>,
+    pub updated_at: DateTime<Utc>,
+    pub replaced_at: DateTime<Utc>,
+    pub links: serde_json::Value,
+    pub tags: serde_json::Value,
+}
+
+#[derive(Queryable, Identifiable, Serialize)]
 pub struct Task {
     pub id: i32, // TODO task.id: i64
     pub title: String,
@@ -57,8 +145,32 @@ pub struct Task {
     pub deadline: Option<DateTime<Utc>>,
     pub weight: Option<f32>,
     pub link: Option<String>,
+    pub notes: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Queryable, Identifiable)]
+pub struct Team {
+    pub id: i32,
+    pub name: String,
+    pub owner: i32,
+}
+
+#[derive(Queryable, Identifiable, Insertable)]
+#[primary_key(team, member)]
+pub struct TeamMember {
+    pub team: i32,
+    pub member: i32,
+}
+
+#[derive(Queryable, Identifiable, Insertable)]
+#[primary_key(team, object)]
+pub struct TeamPermission {
+    pub team: i32,
+    pub object: i32,
+    pub edit: bool,
 }
 
 #[derive(Queryable, Identifiable, Insertable)]
@@ -66,6 +178,8 @@ pub struct Token {
     pub id: uuid::Uuid,
     pub owner: i32,
     pub expires_at: DateTime<Utc>,
+    /// what the token confirms
+    pub tasks: Vec<i32>,
 }
 
 #[derive(Queryable, Identifiable)]
@@ -77,6 +191,14 @@ pub struct User {
     pub timescale: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub locale: String,
+    pub tz: Option<String>,
+    pub digest_at: Option<NaiveTime>,
+    pub digest_sent_at: Option<DateTime<Utc>>,
+    pub dst: String,
+    pub week_start: i32,
+    pub date_format: String,
+    pub default_view: String,
 }
 
 // VARIATIONS
@@ -86,21 +208,293 @@ pub struct User {
 pub struct AuthedUser {
     pub id: i32,
     pub tz: Tz,
+    #[serde(skip)]
+    pub clock: Clock,
+    #[serde(default)]
+    pub settings: Settings,
+}
+
+/// Preferences saved on the server, so that they follow the user across devices.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    pub week_start: u32, // counted from Monday
+    pub date_format: DateFormat,
+    pub default_view: String,
+    pub dst: Dst,
+    pub timescale: Timescale,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            week_start: 0,
+            date_format: DateFormat::Ymd,
+            default_view: String::from("home"),
+            dst: Dst::Earlier,
+            timescale: Timescale::Hours,
+        }
+    }
+}
+
+impl From<&User> for Settings {
+    fn from(user: &User) -> Self {
+        Self {
+            week_start: user.week_start as u32,
+            date_format: user.date_format.clone().into(),
+            default_view: user.default_view.clone(),
+            dst: user.dst.clone().into(),
+            timescale: user.timescale.clone().into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DateFormat {
+    Ymd,
+    Mdy,
+    Dmy,
+}
+
+impl DateFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ymd => "ymd",
+            Self::Mdy => "mdy",
+            Self::Dmy => "dmy",
+        }
+    }
+    pub fn date_pattern(&self) -> &'static str {
+        match self {
+            Self::Ymd => "%Y/%m/%d",
+            Self::Mdy => "%m/%d/%Y",
+            Self::Dmy => "%d/%m/%Y",
+        }
+    }
+    fn pattern(&self) -> String {
+        format!("{}T%H:%M", self.date_pattern())
+    }
+}
+
+impl From<String> for DateFormat {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "mdy" => Self::Mdy,
+            "dmy" => Self::Dmy,
+            _ => Self::Ymd,
+        }
+    }
+}
+
+/// How local times that DST skips or repeats are read.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dst {
+    Earlier, // the earlier of repeated times, skipped times moved forward
+    Later,   // the later of repeated times, skipped times moved forward
+    Strict,  // neither accepted
+}
+
+impl Dst {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Earlier => "earlier",
+            Self::Later => "later",
+            Self::Strict => "strict",
+        }
+    }
+}
+
+impl From<String> for Dst {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "later" => Self::Later,
+            "strict" => Self::Strict,
+            _ => Self::Earlier,
+        }
+    }
+}
+
+/// Unit the user views time in; it buckets the home view, rounds schedules and sets precision.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum Timescale {
+    #[serde(rename = "Y")]
+    Year,
+    #[serde(rename = "Q")]
+    Quarter,
+    #[serde(rename = "M")]
+    Month,
+    #[serde(rename = "W")]
+    Week,
+    #[serde(rename = "D")]
+    Day,
+    #[serde(rename = "6h")]
+    Hours,
+    #[serde(rename = "h")]
+    Hour,
+    #[serde(rename = "15m")]
+    Minutes,
+    #[serde(rename = "m")]
+    Minute,
+    #[serde(rename = "s")]
+    Second,
+}
+
+impl Timescale {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Year => "Y",
+            Self::Quarter => "Q",
+            Self::Month => "M",
+            Self::Week => "W",
+            Self::Day => "D",
+            Self::Hours => "6h",
+            Self::Hour => "h",
+            Self::Minutes => "15m",
+            Self::Minute => "m",
+            Self::Second => "s",
+        }
+    }
+    /// The start of the period containing `local`.
+    pub fn floor(&self, local: NaiveDateTime, week_start: u32) -> NaiveDateTime {
+        let date = local.date();
+        let (h, m) = (local.hour(), local.minute());
+        match self {
+            Self::Year => NaiveDate::from_ymd(date.year(), 1, 1).and_hms(0, 0, 0),
+            Self::Quarter => {
+                NaiveDate::from_ymd(date.year(), (date.month() - 1) / 3 * 3 + 1, 1).and_hms(0, 0, 0)
+            }
+            Self::Month => NaiveDate::from_ymd(date.year(), date.month(), 1).and_hms(0, 0, 0),
+            Self::Week => {
+                let back = (date.weekday().num_days_from_monday() + 7 - week_start) % 7;
+                (date - Duration::days(back as i64)).and_hms(0, 0, 0)
+            }
+            Self::Day => date.and_hms(0, 0, 0),
+            Self::Hours => date.and_hms(h / 6 * 6, 0, 0),
+            Self::Hour => date.and_hms(h, 0, 0),
+            Self::Minutes => date.and_hms(h, m / 15 * 15, 0),
+            Self::Minute => date.and_hms(h, m, 0),
+            Self::Second => date.and_hms(h, m, local.second()),
+        }
+    }
+    /// The end of the period containing `local`, or `local` itself on a boundary.
+    pub fn ceil(&self, local: NaiveDateTime, week_start: u32) -> NaiveDateTime {
+        let start = self.floor(local, week_start);
+        if start == local {
+            return local;
+        }
+        let months = |n: u32| {
+            let m = start.month0() + n;
+            NaiveDate::from_ymd(start.year() + (m / 12) as i32, m % 12 + 1, 1).and_hms(0, 0, 0)
+        };
+        match self {
+            Self::Year => months(12),
+            Self::Quarter => months(3),
+            Self::Month => months(1),
+            Self::Week => start + Duration::weeks(1),
+            Self::Day => start + Duration::days(1),
+            Self::Hours => start + Duration::hours(6),
+            Self::Hour => start + Duration::hours(1),
+            Self::Minutes => start + Duration::minutes(15),
+            Self::Minute => start + Duration::minutes(1),
+            Self::Second => start + Duration::seconds(1),
+        }
+    }
+    /// The nearer of the start and the end of the period containing `local`, the end at a tie.
+    pub fn nearest(&self, local: NaiveDateTime, week_start: u32) -> NaiveDateTime {
+        let (floor, ceil) = (self.floor(local, week_start), self.ceil(local, week_start));
+        if local - floor < ceil - local {
+            floor
+        } else {
+            ceil
+        }
+    }
+    /// The scale schedules are rounded to, which is no coarser than a day.
+    pub fn rounding(&self) -> Self {
+        match self {
+            Self::Year | Self::Quarter | Self::Month | Self::Week => Self::Day,
+            _ => self.clone(),
+        }
+    }
+    /// Time part of dates rendered at this precision, none at a day or coarser.
+    pub fn time_pattern(&self) -> Option<&'static str> {
+        match self {
+            Self::Year | Self::Quarter | Self::Month | Self::Week | Self::Day => None,
+            Self::Second => Some("%H:%M:%S"),
+            _ => Some("%H:%M"),
+        }
+    }
+}
+
+impl From<String> for Timescale {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "Y" => Self::Year,
+            "Q" => Self::Quarter,
+            "M" => Self::Month,
+            "W" => Self::Week,
+            "D" => Self::Day,
+            "h" => Self::Hour,
+            "15m" => Self::Minutes,
+            "m" => Self::Minute,
+            "s" => Self::Second,
+            _ => Self::Hours,
+        }
+    }
+}
+
+/// Source of the current time, given as app data so that tests can stop it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Clock {
+    System,
+    Fixed(DateTime<Utc>),
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::System
+    }
+}
+
+impl Clock {
+    pub fn now(&self) -> DateTime<Utc> {
+        match self {
+            Self::System => Utc::now(),
+            Self::Fixed(now) => *now,
+        }
+    }
 }
 
 impl FromRequest for AuthedUser {
     type Config = ();
     type Error = Error;
-    type Future = Ready<Result<Self, Error>>;
+    type Future = LocalBoxFuture<'static, Result<Self, Error>>;
 
     fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
         use actix_identity::RequestIdentity;
-        if let Some(identity) = req.get_identity() {
-            if let Ok(user) = serde_json::from_str::<Self>(&identity) {
-                return ok(user);
-            }
-        }
-        err(errors::ServiceError::Unauthorized.into())
+
+        let user = req
+            .get_identity()
+            .and_then(|identity| serde_json::from_str::<Self>(&identity).ok());
+        let pool = req.app_data::<web::Data<Pool>>().cloned();
+        let clock = req
+            .app_data::<web::Data<Clock>>()
+            .map(|clock| *clock.get_ref());
+        Box::pin(async move {
+            let (mut user, pool) = match (user, pool) {
+                (Some(user), Some(pool)) => (user, pool),
+                _ => return Err(errors::ServiceError::Unauthorized.into()),
+            };
+            user.clock = clock.unwrap_or_default();
+            // loaded on every request, so that a change on one device reaches the others
+            web::block(move || match user.reloaded(&pool.get().unwrap()) {
+                // left for the handler, e.g. to forget the identity
+                Err(errors::DbError::NotFound) => Ok(user),
+                res => res,
+            })
+            .await
+            .map_err(|_| errors::ServiceError::InternalServerError.into())
+        })
     }
 }
 
@@ -116,6 +510,8 @@ pub struct ResTask {
     pub priority: Option<f32>,
     pub weight: Option<f32>,
     pub link: Option<String>,
+    pub tags: Vec<String>,
+    pub comments: i32,
     pub schedule: Option<Schedule>,
 }
 
@@ -125,6 +521,40 @@ pub struct Schedule {
     pub r: DateTime<Utc>,
 }
 
+#[derive(Debug, PartialEq, Clone, Queryable, Serialize, Deserialize)]
+pub struct Link {
+    pub label: Option<String>,
+    pub url: String,
+}
+
+#[derive(Serialize)]
+pub struct ResAttachment {
+    pub id: i32,
+    pub name: String,
+    pub content_type: String,
+    pub size: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<Attachment> for ResAttachment {
+    fn from(attachment: Attachment) -> Self {
+        Self {
+            id: attachment.id,
+            name: attachment.name,
+            content_type: attachment.content_type,
+            size: attachment.size,
+            created_at: attachment.created_at,
+        }
+    }
+}
+
+/// Tasks scheduled to start in the same period of the user's timescale.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Bucket {
+    pub start: DateTime<Utc>,
+    pub tasks: Vec<i32>,
+}
+
 #[derive(Queryable)]
 pub struct SelTask {
     pub id: i32,
@@ -183,11 +613,47 @@ impl SelTask {
             priority: None,
             weight: self.weight,
             link: self.link,
+            tags: Vec::new(),
+            comments: 0,
             schedule: None,
         }
     }
 }
 
+impl ResTask {
+    /// Fills in what is kept apart from the task rows: their tags and comment counts.
+    pub fn annotate(tasks: &mut [ResTask], conn: &Conn) -> Result<(), errors::ServiceError> {
+        use crate::schema::comments::dsl::{comments, task as commented};
+        use crate::schema::tags::dsl::{name, tags};
+        use crate::schema::task_tags::dsl::{task, task_tags};
+
+        let ids = tasks.iter().map(|t| t.id).collect::<Vec<i32>>();
+        let mut map: HashMap<i32, Vec<String>> = HashMap::new();
+        for (t, n) in task_tags
+            .filter(task.eq_any(&ids))
+            .inner_join(tags)
+            .select((task, name))
+            .order(name)
+            .load::<(i32, String)>(conn)?
+        {
+            map.entry(t).or_default().push(n);
+        }
+        let mut counts: HashMap<i32, i32> = HashMap::new();
+        for t in comments
+            .filter(commented.eq_any(&ids))
+            .select(commented)
+            .load::<i32>(conn)?
+        {
+            *counts.entry(t).or_default() += 1;
+        }
+        for t in tasks.iter_mut() {
+            t.tags = map.remove(&t.id).unwrap_or_default();
+            t.comments = counts.get(&t.id).copied().unwrap_or_default();
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Arrows {
     pub arrows: Vec<Arrow>,
@@ -356,6 +822,7 @@ impl Arrows {
 pub struct EasyDateTime {
     pub date: Option<EasyDate>,
     pub time: Option<EasyTime>,
+    pub rel: Option<EasyRel>, // in place of the date
 }
 #[derive(Debug, Default, PartialEq, PartialOrd)]
 pub struct EasyDate {
@@ -368,17 +835,28 @@ pub struct EasyTime {
     pub h: Option<i32>,
     pub m: Option<i32>,
 }
+#[derive(Debug, PartialEq, PartialOrd)]
+pub enum EasyRel {
+    Days(i64),      // +3d, +2w, today, tomorrow
+    Weekday(u32),   // fri: the next one, counted from Monday
+    EndOfWeek,      // eow: the next first day of the week
+    EndOfMonth,     // eom: the first of the next month
+    Task(i32, i64), // #12+1d: days after the task's deadline or startable
+}
 impl EasyDateTime {
-    fn complete(&self, tz: &Tz) -> Option<NaiveDateTime> {
-        let now = Utc::now().with_timezone(tz).naive_local();
+    /// Completes the omitted parts from `now`, which is the referred task's date for `#12+1d`.
+    pub fn complete(&self, tz: &Tz, now: &DateTime<Utc>, week_start: u32) -> Option<NaiveDateTime> {
+        let now = now.with_timezone(tz).naive_local();
         let mut inherit = false;
-        let time_opt = match &self.time {
-            None => Some(NaiveTime::from_hms(0, 0, 0)),
-            Some(time) => time.complete(&mut inherit, &now),
+        let time_opt = match (&self.time, &self.rel) {
+            (None, Some(EasyRel::Task(..))) => Some(now.time()),
+            (None, _) => Some(NaiveTime::from_hms(0, 0, 0)),
+            (Some(time), _) => time.complete(&mut inherit, &now),
         };
-        let date_opt = match &self.date {
-            None => Some(now.date()),
-            Some(date) => date.complete(&mut inherit, &now),
+        let date_opt = match (&self.date, &self.rel) {
+            (_, Some(rel)) => rel.complete(&now.date(), week_start),
+            (None, None) => Some(now.date()),
+            (Some(date), None) => date.complete(&mut inherit, &now),
         };
         if let (Some(date), Some(time)) = (date_opt, time_opt) {
             return Some(NaiveDateTime::new(date, time));
@@ -386,6 +864,24 @@ impl EasyDateTime {
         None
     }
 }
+impl EasyRel {
+    fn complete(&self, today: &NaiveDate, week_start: u32) -> Option<NaiveDate> {
+        let days = match *self {
+            EasyRel::Days(days) | EasyRel::Task(_, days) => days,
+            EasyRel::Weekday(w) => {
+                ((w + 6 - today.weekday().num_days_from_monday()) % 7 + 1).into()
+            }
+            EasyRel::EndOfWeek => return EasyRel::Weekday(week_start).complete(today, week_start),
+            EasyRel::EndOfMonth => {
+                return match today.month() {
+                    12 => NaiveDate::from_ymd_opt(today.year() + 1, 1, 1),
+                    m => NaiveDate::from_ymd_opt(today.year(), m + 1, 1),
+                }
+            }
+        };
+        today.checked_add_signed(Duration::days(days))
+    }
+}
 impl EasyTime {
     fn complete(&self, inherit: &mut bool, now: &NaiveDateTime) -> Option<NaiveTime> {
         let m = match self.m {
@@ -431,26 +927,117 @@ impl EasyDate {
     }
 }
 impl AuthedUser {
-    pub fn globalize(&self, easy: &EasyDateTime) -> Result<DateTime<Utc>, errors::ServiceError> {
+    pub fn globalize(
+        &self,
+        easy: &EasyDateTime,
+        conn: &Conn,
+    ) -> Result<DateTime<Utc>, errors::ServiceError> {
+        Ok(self.globalize_noted(easy, conn)?.0)
+    }
+    /// Same as `globalize`, also telling how a local time skipped or repeated by DST was read.
+    pub fn globalize_noted(
+        &self,
+        easy: &EasyDateTime,
+        conn: &Conn,
+    ) -> Result<(DateTime<Utc>, Option<String>), errors::ServiceError> {
+        let now = match easy.rel {
+            Some(EasyRel::Task(tid, _)) => self.dated(tid, conn)?,
+            _ => self.clock.now(),
+        };
+        self.globalize_from(easy, &now)
+    }
+    /// Same as `globalize_noted`, counting from `now` instead of the clock or any task.
+    pub fn globalize_from(
+        &self,
+        easy: &EasyDateTime,
+        now: &DateTime<Utc>,
+    ) -> Result<(DateTime<Utc>, Option<String>), errors::ServiceError> {
         let lower = Utc.ymd(1000, 1, 1).and_hms(0, 0, 0);
         let upper = Utc.ymd(9999, 1, 1).and_hms(0, 0, 0);
-        if let Some(local) = easy.complete(&self.tz) {
-            if let Some(dt) = self.tz.from_local_datetime(&local).single() {
-                if lower < dt && dt < upper {
-                    return Ok(dt.with_timezone(&Utc));
+        let local = easy
+            .complete(&self.tz, now, self.settings.week_start)
+            .ok_or_else(|| {
+                errors::ServiceError::BadRequest("failed to interpret datetime.".into())
+            })?;
+        let typed = local
+            .format(&self.settings.date_format.pattern())
+            .to_string();
+        let (dt, note) = match self.tz.from_local_datetime(&local) {
+            LocalResult::Single(dt) => (dt, None),
+            LocalResult::Ambiguous(a, b) => {
+                let (dt, which) = match self.settings.dst {
+                    Dst::Earlier => (min(a, b), "earlier"),
+                    Dst::Later => (max(a, b), "later"),
+                    Dst::Strict => {
+                        return Err(errors::ServiceError::BadRequest(format!(
+                            "{}: repeated by DST.",
+                            typed
+                        )))
+                    }
+                };
+                let note = format!("{} occurs twice by DST, read as the {} one.", typed, which);
+                (dt, Some(note))
+            }
+            LocalResult::None => {
+                if self.settings.dst == Dst::Strict {
+                    return Err(errors::ServiceError::BadRequest(format!(
+                        "{}: skipped by DST.",
+                        typed
+                    )));
                 }
-                return Err(errors::ServiceError::BadRequest(
-                    "some dates are out of range.".into(),
-                ));
+                // read with the offset in effect before the gap, which moves it forward
+                let before = self
+                    .tz
+                    .offset_from_utc_datetime(&(local - Duration::days(1)))
+                    .fix();
+                let dt = before
+                    .from_local_datetime(&local)
+                    .single()
+                    .ok_or_else(|| {
+                        errors::ServiceError::BadRequest("failed to interpret datetime.".into())
+                    })?
+                    .with_timezone(&self.tz);
+                let note = format!(
+                    "{} is skipped by DST, read as {}.",
+                    typed,
+                    self.localize(&dt.with_timezone(&Utc))
+                );
+                (dt, Some(note))
             }
+        };
+        if lower < dt && dt < upper {
+            return Ok((dt.with_timezone(&Utc), note));
         }
         Err(errors::ServiceError::BadRequest(
-            "failed to interpret datetime.".into(),
+            "some dates are out of range.".into(),
         ))
     }
+    /// The deadline, or else the startable, of a task visible to the user.
+    fn dated(&self, tid: i32, conn: &Conn) -> Result<DateTime<Utc>, errors::ServiceError> {
+        let task = self.visible(tid, &self.permitted(false, conn)?, conn)?;
+        task.deadline.or(task.startable).ok_or_else(|| {
+            errors::ServiceError::BadRequest(format!("#{}: no date to count from.", tid))
+        })
+    }
+    /// The user with the timezone and settings saved, which win over the ones of the session.
+    pub fn reloaded(&self, conn: &Conn) -> Result<Self, errors::DbError> {
+        let user = users::table.find(self.id).first::<User>(conn)?;
+        Ok(Self {
+            id: self.id,
+            tz: user
+                .tz
+                .as_ref()
+                .and_then(|tz| tz.parse::<Tz>().ok())
+                .unwrap_or(self.tz),
+            clock: self.clock,
+            settings: Settings::from(&user),
+        })
+    }
     pub fn localize(&self, dt: &DateTime<Utc>) -> String {
         let local = dt.with_timezone(&self.tz).naive_local();
-        local.format("%Y/%m/%dT%H:%M").to_string()
+        local
+            .format(&self.settings.date_format.pattern())
+            .to_string()
     }
 }
 
@@ -479,6 +1066,7 @@ impl From<Allocation> for ResAllocation {
 impl AuthedUser {
     pub fn get_token(
         &self,
+        tasks_: Vec<i32>,
         conn: &Conn,
     ) -> Result<Token, errors::ServiceError> {
         use crate::schema::tokens::dsl::tokens;
@@ -488,7 +1076,8 @@ impl AuthedUser {
                 Token {
                     id: uuid::Uuid::new_v4(),
                     owner: self.id,
-                    expires_at: chrono::Utc::now() + chrono::Duration::minutes(1),
+                    expires_at: self.clock.now() + chrono::Duration::minutes(1),
+                    tasks: tasks_,
                 }
             ).get_result::<Token>(conn)?
         )
@@ -497,21 +1086,428 @@ impl AuthedUser {
         &self,
         token: uuid::Uuid,
         conn: &Conn
-    ) -> Result<(), errors::ServiceError> {
+    ) -> Result<Token, errors::ServiceError> {
         use crate::schema::tokens::dsl::{expires_at, owner, tokens};
 
-        diesel::delete(
-            tokens.filter(expires_at.lt(&chrono::Utc::now()))
-        ).execute(conn)?;
         if let Ok(token) = tokens
             .find(&token)
             .filter(owner.eq(&self.id))
+            .filter(expires_at.gt(&self.clock.now()))
             .first::<Token>(conn) {
                 diesel::delete(&token).execute(conn)?;
-                return Ok(());
+                return Ok(token);
             }
         Err(errors::ServiceError::BadRequest(
             "one-time token invalid.".into()
         ))
     }
 }
+
+/// What a user may see, or edit, beyond users who granted them directly in `permissions`.
+pub struct Permitted {
+    subject: i32,
+    edit: bool,
+    /// users who granted a team the user belongs to
+    pub users: Vec<i32>,
+    /// tasks in trees shared with the user
+    pub tasks: Vec<i32>,
+}
+
+impl Permitted {
+    pub fn covers(&self, task: &Task) -> bool {
+        self.users.contains(&task.assign) || self.tasks.contains(&task.id)
+    }
+    /// Tasks the user may view, or edit, whether granted directly, through a team, or shared.
+    pub fn filter(&self) -> TaskFilter {
+        use crate::schema::permissions::dsl::{edit, object, permissions, subject};
+        use crate::schema::tasks::dsl::{assign, id};
+        use diesel::dsl::exists;
+
+        let granted = permissions
+            .filter(subject.eq(self.subject))
+            .filter(object.eq(assign));
+        let others = assign
+            .eq_any(self.users.clone())
+            .or(id.eq_any(self.tasks.clone()));
+        if self.edit {
+            Box::new(exists(granted.filter(edit)).or(others))
+        } else {
+            Box::new(exists(granted).or(others))
+        }
+    }
+}
+
+impl AuthedUser {
+    pub fn permitted(&self, edit_: bool, conn: &Conn) -> Result<Permitted, errors::ServiceError> {
+        Ok(Permitted {
+            subject: self.id,
+            edit: edit_,
+            users: self.permitted_users(edit_, conn)?,
+            tasks: self.permitted_tasks(edit_, conn)?,
+        })
+    }
+    fn permitted_users(&self, edit_: bool, conn: &Conn) -> Result<Vec<i32>, errors::ServiceError> {
+        use crate::schema::team_members::dsl::{member, team as member_of, team_members};
+        use crate::schema::team_permissions::dsl::{edit, object, team, team_permissions};
+
+        let mut query = team_permissions
+            .filter(team.eq_any(team_members.filter(member.eq(&self.id)).select(member_of)))
+            .select(object)
+            .distinct()
+            .into_boxed();
+        if edit_ {
+            query = query.filter(edit)
+        }
+        Ok(query.load::<i32>(conn)?)
+    }
+    /// Each granted task and all its descendants.
+    fn permitted_tasks(&self, edit_: bool, conn: &Conn) -> Result<Vec<i32>, errors::ServiceError> {
+        use crate::schema::arrows::dsl::arrows;
+        use crate::schema::task_permissions::dsl::{edit, subject, task, task_permissions};
+
+        let mut query = task_permissions
+            .filter(subject.eq(&self.id))
+            .select(task)
+            .into_boxed();
+        if edit_ {
+            query = query.filter(edit)
+        }
+        let roots = query.load::<i32>(conn)?;
+        if roots.is_empty() {
+            return Ok(roots);
+        }
+        let _arrows: Arrows = arrows.load::<Arrow>(conn)?.into();
+        let mut ids = roots
+            .iter()
+            .flat_map(|root| Tid::from(*root).nodes_to(LR::Leaf, &_arrows))
+            .collect::<Vec<i32>>();
+        ids.sort();
+        ids.dedup();
+        Ok(ids)
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "audits"]
+struct NewAudit<'a> {
+    actor: i32,
+    owner: i32,
+    action: &'a str,
+    task: Option<i32>,
+    old_values: Option<serde_json::Value>,
+    new_values: Option<serde_json::Value>,
+}
+
+impl AuthedUser {
+    /// Append to the audit log; call this within the transaction making the change.
+    pub fn audit(
+        &self,
+        action: &str,
+        owner: i32,
+        task: Option<i32>,
+        old: Option<serde_json::Value>,
+        new: Option<serde_json::Value>,
+        conn: &Conn,
+    ) -> Result<(), errors::ServiceError> {
+        use crate::schema::audits::dsl::audits;
+
+        diesel::insert_into(audits)
+            .values(&NewAudit {
+                actor: self.id,
+                owner: owner,
+                action: action,
+                task: task,
+                old_values: old,
+                new_values: new,
+            })
+            .execute(conn)?;
+        Ok(())
+    }
+    /// Audit a task change, keeping only the fields that differ.
+    pub fn audit_task(
+        &self,
+        action: &str,
+        old: Option<&Task>,
+        new: Option<&Task>,
+        conn: &Conn,
+    ) -> Result<(), errors::ServiceError> {
+        let to_map = |t: Option<&Task>| match t.map(serde_json::to_value) {
+            Some(Ok(serde_json::Value::Object(mut map))) => {
+                map.remove("updated_at");
+                map
+            }
+            _ => serde_json::Map::new(),
+        };
+        let (mut old_map, mut new_map) = (to_map(old), to_map(new));
+        if old.is_some() && new.is_some() {
+            let same = old_map
+                .iter()
+                .filter(|(k, v)| new_map.get(*k) == Some(v))
+                .map(|(k, _)| k.clone())
+                .collect::<Vec<String>>();
+            for k in same {
+                old_map.remove(&k);
+                new_map.remove(&k);
+            }
+        }
+        let task = old.or(new).unwrap();
+        self.audit(
+            action,
+            task.assign,
+            Some(task.id),
+            old.map(|_| serde_json::Value::Object(old_map)),
+            new.map(|_| serde_json::Value::Object(new_map)),
+            conn,
+        )
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "notifications"]
+struct NewNotification<'a> {
+    owner: i32,
+    actor: i32,
+    kind: &'a str,
+    task: Option<i32>,
+    created_at: DateTime<Utc>,
+}
+
+impl AuthedUser {
+    /// Leave a notification for `owner`, unless they are the one acting.
+    pub fn notify(
+        &self,
+        owner: i32,
+        kind: &str,
+        task: Option<i32>,
+        conn: &Conn,
+    ) -> Result<(), errors::ServiceError> {
+        use crate::schema::notifications::dsl::notifications;
+
+        if owner == self.id {
+            return Ok(());
+        }
+        diesel::insert_into(notifications)
+            .values(&NewNotification {
+                owner: owner,
+                actor: self.id,
+                kind: kind,
+                task: task,
+                created_at: self.clock.now(),
+            })
+            .execute(conn)?;
+        Ok(())
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "task_versions"]
+struct NewTaskVersion<'a> {
+    task: i32,
+    title: &'a str,
+    assign: i32,
+    is_starred: bool,
+    startable: Option<DateTime<Utc>>,
+    deadline: Option<DateTime<Utc>>,
+    weight: Option<f32>,
+    link: Option<&'a str>,
+    notes: Option<&'a str>,
+    updated_at: DateTime<Utc>,
+    links: serde_json::Value,
+    tags: serde_json::Value,
+}
+
+#[derive(Insertable)]
+#[table_name = "task_links"]
+struct NewLink<'a> {
+    task: i32,
+    label: Option<&'a str>,
+    url: &'a str,
+}
+
+impl Task {
+    /// Keep this row as a version before it gets updated.
+    pub fn snapshot(&self, conn: &Conn) -> Result<(), errors::ServiceError> {
+        use crate::schema::task_versions::dsl::task_versions;
+
+        diesel::insert_into(task_versions)
+            .values(&NewTaskVersion {
+                task: self.id,
+                title: &self.title,
+                assign: self.assign,
+                is_starred: self.is_starred,
+                startable: self.startable,
+                deadline: self.deadline,
+                weight: self.weight,
+                link: self.link.as_deref(),
+                notes: self.notes.as_deref(),
+                updated_at: self.updated_at,
+                links: serde_json::json!(self.links(conn)?),
+                tags: serde_json::json!(self.tags(conn)?),
+            })
+            .execute(conn)?;
+        Ok(())
+    }
+    pub fn links(&self, conn: &Conn) -> Result<Vec<Link>, errors::ServiceError> {
+        use crate::schema::task_links::dsl::{id, label, task, task_links, url};
+
+        Ok(task_links
+            .filter(task.eq(&self.id))
+            .order(id)
+            .select((label, url))
+            .load::<Link>(conn)?)
+    }
+    /// Replaces the links, the first of which is also kept in `link`.
+    pub fn set_links(&self, links: &[Link], conn: &Conn) -> Result<(), errors::ServiceError> {
+        use crate::schema::task_links::dsl::{task, task_links};
+
+        diesel::delete(task_links.filter(task.eq(&self.id))).execute(conn)?;
+        diesel::insert_into(task_links)
+            .values(
+                &links
+                    .iter()
+                    .map(|l| NewLink {
+                        task: self.id,
+                        label: l.label.as_deref(),
+                        url: &l.url,
+                    })
+                    .collect::<Vec<NewLink>>(),
+            )
+            .execute(conn)?;
+        Ok(())
+    }
+    pub fn tags(&self, conn: &Conn) -> Result<Vec<String>, errors::ServiceError> {
+        use crate::schema::tags::dsl::{name, tags};
+        use crate::schema::task_tags::dsl::{task, task_tags};
+
+        Ok(task_tags
+            .filter(task.eq(&self.id))
+            .inner_join(tags)
+            .select(name)
+            .order(name)
+            .load::<String>(conn)?)
+    }
+    /// Replaces the tags with those of the names, which belong to the assignee.
+    pub fn set_tags(&self, names: &[String], conn: &Conn) -> Result<(), errors::ServiceError> {
+        use crate::schema::tags::dsl::{id, name, owner, tags};
+        use crate::schema::task_tags::dsl::{task, task_tags};
+
+        diesel::delete(task_tags.filter(task.eq(&self.id))).execute(conn)?;
+        if names.is_empty() {
+            return Ok(());
+        }
+        diesel::insert_into(tags)
+            .values(
+                names
+                    .iter()
+                    .map(|n| (owner.eq(self.assign), name.eq(n)))
+                    .collect::<Vec<_>>(),
+            )
+            .on_conflict((owner, name))
+            .do_nothing()
+            .execute(conn)?;
+        let ids = tags
+            .filter(owner.eq(&self.assign))
+            .filter(name.eq_any(names))
+            .select(id)
+            .load::<i32>(conn)?;
+        diesel::insert_into(task_tags)
+            .values(
+                ids.into_iter()
+                    .map(|t| TaskTag {
+                        task: self.id,
+                        tag: t,
+                    })
+                    .collect::<Vec<TaskTag>>(),
+            )
+            .execute(conn)?;
+        Ok(())
+    }
+}
+
+impl Invitation {
+    pub fn tz(&self) -> Result<Tz, errors::ServiceError> {
+        self.tz.parse::<Tz>().map_err(|e| {
+            println!("Invalid timezone in invitation {}: {}", self.id, e);
+            errors::ServiceError::InternalServerError
+        })
+    }
+}
+
+impl TaskVersion {
+    /// Versions kept before links were listed hold the single `link` only.
+    pub fn links(&self) -> Vec<Link> {
+        match serde_json::from_value::<Vec<Link>>(self.links.clone()) {
+            Ok(links) if !links.is_empty() => links,
+            _ => self
+                .link
+                .iter()
+                .map(|url| Link {
+                    label: None,
+                    url: url.clone(),
+                })
+                .collect(),
+        }
+    }
+    /// Versions kept before tags were there hold none.
+    pub fn tags(&self) -> Vec<String> {
+        serde_json::from_value::<Vec<String>>(self.tags.clone()).unwrap_or_default()
+    }
+}
+
+impl AuthedUser {
+    /// The task, if it is live and the user may edit it.
+    pub fn editable(
+        &self,
+        tid: i32,
+        permitted: &Permitted,
+        conn: &Conn,
+    ) -> Result<Task, errors::ServiceError> {
+        use crate::schema::tasks::dsl::{deleted_at, tasks};
+
+        tasks
+            .find(tid)
+            .filter(deleted_at.is_null())
+            .filter(permitted.filter())
+            .first::<Task>(conn)
+            .map_err(|_| {
+                errors::ServiceError::BadRequest(format!(
+                    "#{}: item not found, or no edit permission.",
+                    tid,
+                ))
+            })
+    }
+    /// The task, if it is live and the user may view it.
+    pub fn visible(
+        &self,
+        tid: i32,
+        permitted: &Permitted,
+        conn: &Conn,
+    ) -> Result<Task, errors::ServiceError> {
+        use crate::schema::tasks::dsl::{deleted_at, tasks};
+
+        tasks
+            .find(tid)
+            .filter(deleted_at.is_null())
+            .filter(permitted.filter())
+            .first::<Task>(conn)
+            .map_err(|_| errors::ServiceError::BadRequest(format!("#{}: item not found.", tid)))
+    }
+    pub fn assignable(
+        &self,
+        assign: i32,
+        permitted: &Permitted,
+        conn: &Conn,
+    ) -> Result<bool, errors::ServiceError> {
+        use crate::schema::permissions::dsl::*;
+        use diesel::dsl::{exists, select};
+
+        Ok(permitted.users.contains(&assign)
+            || select(exists(
+                permissions
+                    .filter(subject.eq(&self.id))
+                    .filter(object.eq(&assign))
+                    .filter(edit),
+            ))
+            .get_result(conn)?)
+    }
+}
@@ -14,6 +14,43 @@ table! {
     }
 }
 
+table! {
+    attachments (id) {
+        id -> Int4,
+        task -> Int4,
+        name -> Varchar,
+        content_type -> Varchar,
+        size -> Int4,
+        digest -> Varchar,
+        uploaded_by -> Int4,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    audits (id) {
+        id -> Int4,
+        actor -> Nullable<Int4>,
+        owner -> Nullable<Int4>,
+        action -> Varchar,
+        task -> Nullable<Int4>,
+        old_values -> Nullable<Jsonb>,
+        new_values -> Nullable<Jsonb>,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    comments (id) {
+        id -> Int4,
+        task -> Int4,
+        author -> Int4,
+        body -> Varchar,
+        created_at -> Timestamptz,
+        edited_at -> Nullable<Timestamptz>,
+    }
+}
+
 table! {
     invitations (id) {
         id -> Uuid,
@@ -21,6 +58,29 @@ table! {
         expires_at -> Timestamptz,
         forgot_pw -> Bool,
         tz -> Varchar,
+        locale -> Varchar,
+        owner -> Nullable<Int4>,
+    }
+}
+
+table! {
+    notifications (id) {
+        id -> Int4,
+        owner -> Int4,
+        actor -> Nullable<Int4>,
+        kind -> Varchar,
+        task -> Nullable<Int4>,
+        created_at -> Timestamptz,
+        read_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    permission_requests (subject, object) {
+        subject -> Int4,
+        object -> Int4,
+        edit -> Bool,
+        created_at -> Timestamptz,
     }
 }
 
@@ -32,6 +92,66 @@ table! {
     }
 }
 
+table! {
+    searches (id) {
+        id -> Int4,
+        owner -> Int4,
+        name -> Varchar,
+        condition -> Varchar,
+    }
+}
+
+table! {
+    tags (id) {
+        id -> Int4,
+        owner -> Int4,
+        name -> Varchar,
+    }
+}
+
+table! {
+    task_links (id) {
+        id -> Int4,
+        task -> Int4,
+        label -> Nullable<Varchar>,
+        url -> Varchar,
+    }
+}
+
+table! {
+    task_permissions (subject, task) {
+        subject -> Int4,
+        task -> Int4,
+        edit -> Bool,
+    }
+}
+
+table! {
+    task_tags (task, tag) {
+        task -> Int4,
+        tag -> Int4,
+    }
+}
+
+table! {
+    task_versions (id) {
+        id -> Int4,
+        task -> Int4,
+        title -> Varchar,
+        assign -> Int4,
+        is_starred -> Bool,
+        startable -> Nullable<Timestamptz>,
+        deadline -> Nullable<Timestamptz>,
+        weight -> Nullable<Float4>,
+        link -> Nullable<Varchar>,
+        notes -> Nullable<Varchar>,
+        updated_at -> Timestamptz,
+        replaced_at -> Timestamptz,
+        links -> Jsonb,
+        tags -> Jsonb,
+    }
+}
+
 table! {
     tasks (id) {
         id -> Int4,
@@ -43,8 +163,33 @@ table! {
         deadline -> Nullable<Timestamptz>,
         weight -> Nullable<Float4>,
         link -> Nullable<Varchar>,
+        notes -> Nullable<Varchar>,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    team_members (team, member) {
+        team -> Int4,
+        member -> Int4,
+    }
+}
+
+table! {
+    team_permissions (team, object) {
+        team -> Int4,
+        object -> Int4,
+        edit -> Bool,
+    }
+}
+
+table! {
+    teams (id) {
+        id -> Int4,
+        name -> Varchar,
+        owner -> Int4,
     }
 }
 
@@ -53,6 +198,14 @@ table! {
         id -> Uuid,
         owner -> Int4,
         expires_at -> Timestamptz,
+        tasks -> Array<Int4>,
+    }
+}
+
+table! {
+    trashed_arrows (source, target) {
+        source -> Int4,
+        target -> Int4,
     }
 }
 
@@ -65,19 +218,60 @@ table! {
         timescale -> Varchar,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        locale -> Varchar,
+        tz -> Nullable<Varchar>,
+        digest_at -> Nullable<Time>,
+        digest_sent_at -> Nullable<Timestamptz>,
+        dst -> Varchar,
+        week_start -> Int4,
+        date_format -> Varchar,
+        default_view -> Varchar,
     }
 }
 
 joinable!(allocations -> users (owner));
+joinable!(attachments -> tasks (task));
+joinable!(attachments -> users (uploaded_by));
+joinable!(comments -> tasks (task));
+joinable!(comments -> users (author));
+joinable!(invitations -> users (owner));
+joinable!(searches -> users (owner));
+joinable!(tags -> users (owner));
+joinable!(task_links -> tasks (task));
+joinable!(task_permissions -> tasks (task));
+joinable!(task_permissions -> users (subject));
+joinable!(task_tags -> tags (tag));
+joinable!(task_tags -> tasks (task));
+joinable!(task_versions -> tasks (task));
+joinable!(task_versions -> users (assign));
 joinable!(tasks -> users (assign));
+joinable!(team_members -> teams (team));
+joinable!(team_members -> users (member));
+joinable!(team_permissions -> teams (team));
+joinable!(teams -> users (owner));
 joinable!(tokens -> users (owner));
 
 allow_tables_to_appear_in_same_query!(
     allocations,
     arrows,
+    attachments,
+    audits,
+    comments,
     invitations,
+    notifications,
+    permission_requests,
     permissions,
+    searches,
+    tags,
+    task_links,
+    task_permissions,
+    task_tags,
+    task_versions,
     tasks,
+    team_members,
+    team_permissions,
+    teams,
     tokens,
+    trashed_arrows,
     users,
 );
@@ -27,3 +27,80 @@ pub fn verify(hash: &str, password: &str) -> Result<bool, errors::ServiceError>
         errors::ServiceError::InternalServerError
     })
 }
+
+/// Fixtures for tests that need `DATABASE_URL`, which are skipped without it.
+#[cfg(test)]
+pub mod testing {
+    use chrono::NaiveTime;
+    use diesel::prelude::*;
+    use diesel::r2d2::{self, ConnectionManager, CustomizeConnection};
+
+    use crate::models;
+
+    #[derive(Debug)]
+    struct RolledBack;
+
+    impl CustomizeConnection<PgConnection, r2d2::Error> for RolledBack {
+        fn on_acquire(&self, conn: &mut PgConnection) -> Result<(), r2d2::Error> {
+            conn.begin_test_transaction()
+                .map_err(r2d2::Error::QueryError)
+        }
+    }
+
+    /// A pool of a single connection, none of whose changes are ever committed.
+    pub fn pool() -> Option<models::Pool> {
+        dotenv::dotenv().ok();
+        let url = match std::env::var("DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => {
+                println!("DATABASE_URL not set, skipped.");
+                return None;
+            }
+        };
+        Some(
+            r2d2::Pool::builder()
+                .max_size(1)
+                .connection_customizer(Box::new(RolledBack))
+                .build(ConnectionManager::<PgConnection>::new(url))
+                .expect("Failed to create pool."),
+        )
+    }
+
+    /// A user as registered, who may edit their own items.
+    pub fn user(name_: &str, conn: &models::Conn) -> models::AuthedUser {
+        use crate::schema::allocations::dsl::allocations;
+        use crate::schema::permissions::dsl::permissions;
+        use crate::schema::users::dsl::{email, hash, name, users};
+
+        let user = diesel::insert_into(users)
+            .values((
+                email.eq(format!("{}@example.com", name_)),
+                hash.eq(""),
+                name.eq(name_),
+            ))
+            .get_result::<models::User>(conn)
+            .unwrap();
+        diesel::insert_into(permissions)
+            .values(&models::Permission {
+                subject: user.id,
+                object: user.id,
+                edit: true,
+            })
+            .execute(conn)
+            .unwrap();
+        diesel::insert_into(allocations)
+            .values(&models::Allocation {
+                owner: user.id,
+                open: NaiveTime::from_hms(9, 0, 0),
+                hours: 6,
+            })
+            .execute(conn)
+            .unwrap();
+        models::AuthedUser {
+            id: user.id,
+            tz: chrono_tz::Tz::UTC,
+            clock: models::Clock::default(),
+            settings: models::Settings::from(&user),
+        }
+    }
+}